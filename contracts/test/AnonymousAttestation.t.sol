@@ -84,6 +84,15 @@ contract AttestationTest is Test {
         );
         assertEq(attestation.revocationTime, 0, "Revocation time should be 0");
 
+        // the relayer reads the nonce mapping at slot 6
+        assertEq(
+            vm.load(
+                address(anonymousAttester),
+                keccak256(abi.encode(bytes32(uint256(123456789)), uint256(6)))
+            ),
+            bytes32(uint256(1))
+        );
+
         // Revoke attestation
         anonymousAttester.revoke(
             schema,
@@ -153,4 +162,33 @@ contract AttestationTest is Test {
             })
         );
     }
+
+    function test_StorageLayout() public view {
+        address attester = address(anonymousAttester);
+        assertEq(
+            vm.load(attester, bytes32(uint256(0))),
+            bytes32(uint256(uint160(address(this))))
+        );
+        assertEq(
+            vm.load(attester, bytes32(uint256(1))),
+            bytes32(uint256(uint160(address(anonymousAttester.verifier()))))
+        );
+        assertEq(
+            vm.load(attester, bytes32(uint256(2))),
+            anonymousAttester.curiaPubkey(0)
+        );
+        assertEq(
+            vm.load(attester, bytes32(uint256(3))),
+            anonymousAttester.curiaPubkey(1)
+        );
+        // schemas mapping at slot 5
+        assertEq(
+            vm.load(attester, keccak256(abi.encode(schema, uint256(5)))),
+            bytes32(uint256(1))
+        );
+        assertEq(
+            vm.load(attester, keccak256(abi.encode(bytes32(0), uint256(5)))),
+            bytes32(0)
+        );
+    }
 }
@@ -117,7 +117,7 @@ export const AttestationCard = ({
           args: [eas.schema, attestation.id, pad(toHex(revoker))],
         })
 
-        hash = await proxyAnonymousAttestation(calldata)
+        hash = await proxyAnonymousAttestation(calldata, chainId)
       } else {
         // revoke attestation
         hash = await walletClient.data?.writeContract({
@@ -2,7 +2,11 @@
 
 import { useMemo, useState } from "react"
 import { Abis, Addresses, EAS } from "@/constants/contracts"
-import { getCuriaSignature, proxyAnonymousAttestation } from "@/services/curia"
+import {
+  getCuriaNonce,
+  getCuriaSignature,
+  proxyAnonymousAttestation,
+} from "@/services/curia"
 import { zodResolver } from "@hookform/resolvers/zod"
 import { useQueryClient } from "@tanstack/react-query"
 import axios from "axios"
@@ -16,6 +20,7 @@ import {
   encodeFunctionData,
   zeroHash,
 } from "viem"
+import { createSiweMessage } from "viem/siwe"
 import { useAccount, useChainId, usePublicClient, useWalletClient } from "wagmi"
 import { z } from "zod"
 
@@ -120,16 +125,28 @@ export const EndorsePublicCard = () => {
         if (!account.address) return
         if (!data.anonymous.password) return
 
-        // get verfication signature
+        // get verfication signature over a one-time SIWE challenge
+        const { nonce: challenge, domain } = await getCuriaNonce()
+        const siweMessage = createSiweMessage({
+          address: account.address,
+          chainId,
+          domain,
+          nonce: challenge,
+          uri: window.location.origin,
+          version: "1",
+          statement: "Verify account ownership with Curia",
+        })
         const signature = await walletClient.data?.signMessage({
-          message: `CURIA VERIFY ACCOUNT OWNERSHIP ${account.address}`,
+          message: siweMessage,
         })
         if (!signature) return
         // get curia signature
         const curiaSignature = await getCuriaSignature(
           account.address,
           signature,
-          "ECDSA"
+          siweMessage,
+          "ECDSA",
+          chainId
         )
         // get signature for this role
         const signatureForThisRole = curiaSignature.signatures.find(
@@ -180,7 +197,7 @@ export const EndorsePublicCard = () => {
           ],
         })
 
-        hash = await proxyAnonymousAttestation(calldata)
+        hash = await proxyAnonymousAttestation(calldata, chainId)
       } else {
         hash = await walletClient.data?.writeContract({
           abi: Abis.EAS_ABI,
@@ -1,16 +1,37 @@
 import axios from "axios"
-import { Address, fromHex, Hex } from "viem"
+import {
+  Address,
+  encodePacked,
+  fromHex,
+  Hex,
+  hexToBigInt,
+  keccak256,
+} from "viem"
 
 import { env } from "@/env.mjs"
 import { CuriaSignature } from "@/types/signature"
 
+export const getCuriaNonce = async () => {
+  const response = await axios.get(`${env.NEXT_PUBLIC_CURIA_API_URL}/nonce`)
+
+  return response.data as {
+    nonce: string
+    domain: string
+    expires_at: number
+  }
+}
+
 export const getCuriaSignature = async (
   address: Address,
   signature: Hex,
-  type: "ECDSA" | "EDDSA"
+  message: string,
+  type: "ECDSA" | "EDDSA",
+  chainId: number
 ) => {
   const body = {
     address,
+    message,
+    chain_id: chainId,
     signature: {
       [type]: {
         r: `0x${signature.slice(2, 66)}`,
@@ -31,9 +52,57 @@ export const getCuriaSignature = async (
   }
 }
 
-export const proxyAnonymousAttestation = async (callData: Hex) => {
+// hashcash stamp over the calldata, required by the relayer
+const solveRelayPow = async (callData: Hex, chainId: number) => {
+  const { difficulty, domain } = (
+    await axios.get(`${env.NEXT_PUBLIC_CURIA_API_URL}/pow`)
+  ).data as { difficulty: number; domain: string }
+
+  const timestamp = BigInt(Math.floor(Date.now() / 1000))
+  const inputHash = keccak256(callData)
+  const target = 1n << BigInt(256 - difficulty)
+  for (let nonce = 0n; ; nonce++) {
+    const digest = keccak256(
+      encodePacked(
+        ["string", "uint64", "uint64", "uint64", "bytes32"],
+        [domain, BigInt(chainId), timestamp, nonce, inputHash]
+      )
+    )
+    if (hexToBigInt(digest) < target) {
+      return { timestamp: Number(timestamp), nonce: Number(nonce) }
+    }
+  }
+}
+
+export const proxyAnonymousAttestation = async (
+  callData: Hex,
+  chainId: number
+) => {
   const response = await axios.post(`${env.NEXT_PUBLIC_CURIA_API_URL}/proxy`, {
     input: callData,
+    chain_id: chainId,
+    pow: await solveRelayPow(callData, chainId),
   })
-  return response.data.tx_hash as Hex
+
+  // the relayer queues the call, wait for its first broadcast
+  let job = response.data as RelayJob
+  while (job.status === "queued") {
+    await new Promise((resolve) => setTimeout(resolve, 2000))
+    job = (
+      await axios.get(`${env.NEXT_PUBLIC_CURIA_API_URL}/proxy/${job.id}`)
+    ).data as RelayJob
+  }
+  if (!job.tx_hash) {
+    throw new Error(job.reason ?? `Relay ${job.status}`)
+  }
+
+  return job.tx_hash
+}
+
+type RelayJob = {
+  id: string
+  status: "queued" | "pending" | "mined" | "reverted" | "replaced" | "failed"
+  tx_hash?: Hex
+  uid?: Hex
+  reason?: string
 }
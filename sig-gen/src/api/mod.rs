@@ -1,88 +1,394 @@
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use alloy::{
-    primitives::{FixedBytes, U256},
-    providers::{
-        network::{EthereumWallet, TransactionBuilder},
-        Provider, ProviderBuilder,
-    },
-    rpc::types::TransactionRequest,
-    signers::{k256::ecdsa::RecoveryId, local::PrivateKeySigner, Signature as AlloySignature},
+    primitives::{Bytes, B256, U256},
+    providers::Provider,
+    signers::{k256::ecdsa::RecoveryId, Signature as AlloySignature},
 };
 use anyhow::Result;
+use ark_ec::{AffineRepr, CurveGroup};
 use ark_ff::{BigInteger, PrimeField, UniformRand};
 use ark_std::rand::rngs::OsRng;
 use axum::{
-    extract::{Json, State as AState},
-    routing::post,
+    extract::{Json, Path, Query, State as AState},
+    routing::{get, post},
     Router,
 };
-use futures::future::try_join_all;
 use hyper::StatusCode;
 use serde_json::{json, Value};
+use tokio::task::spawn_blocking;
 
 use crate::{
-    crypto::{eddsa_sign, eddsa_verify_message, hash, EdAffine},
-    query::ALL_ROLES,
+    contracts::RegistryClient,
+    crypto::{eddsa_sign, eddsa_verify_message, pubkey_words, EdAffine, Identity},
+    issuance::EpochKey,
+    query::Snapshot,
+    relayer::{check_proof, decode, preflight, Job, RelayError, RelayerPool, POW_DOMAIN},
 };
 
+pub mod siwe;
+pub use siwe::*;
 pub mod types;
 pub use types::*;
+pub mod wallet;
+pub use wallet::*;
 
 pub fn router() -> Router<State> {
     Router::new()
+        .route("/nonce", get(nonce))
         .route("/signature", post(signature))
+        .route("/anonymity", get(anonymity))
         .route("/proxy", post(proxy))
+        .route("/proxy/:id", get(proxy_status))
+        .route("/relayer", get(relayer_status))
+        .route("/health", get(health))
+        .route("/pow", get(pow))
+        .route("/user-op/:hash", get(user_op_status))
 }
 
 pub async fn proxy(
     AState(state): AState<State>,
-    Json(ProxyTransactionRequest { input, is_testnet }): Json<ProxyTransactionRequest>,
+    Json(ProxyTransactionRequest {
+        input,
+        chain_id,
+        mix,
+        pow,
+        user_op,
+    }): Json<ProxyTransactionRequest>,
 ) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
-    let provider = state.provider(is_testnet).map_err(|e| {
+    let network = state.network(chain_id).map_err(|e| {
         (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({ "message": format!("Failed to get provider: {}", e) })),
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "message": format!("Unsupported network: {}", e) })),
+        )
+    })?;
+    let anonymous_attestator = network.anonymous_attestator;
+
+    state.gate.check(network.chain_id, &input, pow).await?;
+    let call = decode(&input)?;
+    if network.native_proofs {
+        // the server key is the attester's curiaPubkey
+        let pubkey = pubkey_words(&(EdAffine::generator() * state.private_key).into_affine());
+        spawn_blocking(move || check_proof(pubkey, &call))
+            .await
+            .map_err(|e| {
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(json!({ "message": format!("Failed to verify the proof: {}", e) })),
+                )
+            })??;
+    }
+    if user_op {
+        if mix {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "message": "User operations cannot be mixed" })),
+            ));
+        }
+        return proxy_user_op(network, input).await;
+    }
+    if mix && network.relayer.config.mixing.is_none() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "message": "Mixing is not enabled on this network" })),
+        ));
+    }
+
+    let relayer = network.relayer.pick().await?;
+    preflight(
+        &network.provider,
+        anonymous_attestator,
+        relayer.address,
+        &input,
+    )
+    .await?;
+
+    let job = relayer
+        .submit(anonymous_attestator, input, mix)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "message": format!("Failed to queue transaction: {}", e) })),
+            )
+        })?;
+
+    Ok(Json(job_response(&network.relayer, job)))
+}
+
+async fn proxy_user_op(
+    network: &Network,
+    input: Bytes,
+) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    let user_ops = network.user_ops.as_ref().ok_or_else(|| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "message": "User operations are not enabled on this network" })),
+        )
+    })?;
+
+    preflight(
+        &network.provider,
+        network.anonymous_attestator,
+        user_ops.config.account,
+        &input,
+    )
+    .await?;
+
+    let fees = network
+        .provider
+        .estimate_eip1559_fees(None)
+        .await
+        .map_err(|e| RelayError::Rpc(e.to_string()))?;
+    let user_op_hash = user_ops
+        .submit(
+            network.anonymous_attestator,
+            input,
+            (fees.max_fee_per_gas, fees.max_priority_fee_per_gas),
+        )
+        .await?;
+
+    Ok(Json(json!({
+        "user_op_hash": user_op_hash,
+        "chain_id": network.chain_id,
+        "status": "pending",
+    })))
+}
+
+pub async fn user_op_status(
+    AState(state): AState<State>,
+    Path(user_op_hash): Path<B256>,
+    Query(ChainQuery { chain_id }): Query<ChainQuery>,
+) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    let network = state.network(chain_id).map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "message": format!("Unsupported network: {}", e) })),
+        )
+    })?;
+    let user_ops = network.user_ops.as_ref().ok_or_else(|| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "message": "User operations are not enabled on this network" })),
         )
     })?;
 
-    let anonymous_attestator = state.anonymous_attestator(is_testnet).map_err(|e| {
+    let receipt = user_ops.receipt(user_op_hash).await.map_err(|e| {
         (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({ "message": format!("Failed to get anonymous attestator: {}", e) })),
+            StatusCode::BAD_GATEWAY,
+            Json(json!({ "message": format!("Failed to get user operation receipt: {}", e) })),
         )
     })?;
 
-    let tx_hash = async {
-        let provider = ProviderBuilder::new()
-            .with_recommended_fillers()
-            .wallet(EthereumWallet::new(PrivateKeySigner::from_bytes(
-                &state.proxy_private_key,
-            )?))
-            .on_provider(provider);
-        let tx_request = TransactionRequest::default()
-            .to(anonymous_attestator)
-            .with_input(input);
-        let tx_hash = *provider.send_transaction(tx_request).await?.tx_hash();
-        anyhow::Ok(tx_hash)
+    Ok(Json(match receipt {
+        Some(receipt) => json!({
+            "user_op_hash": user_op_hash,
+            "chain_id": network.chain_id,
+            "status": if receipt.success { "mined" } else { "reverted" },
+            "tx_hash": receipt.receipt.transaction_hash,
+            "block_number": receipt.receipt.block_number,
+            "uid": receipt.uid(network.anonymous_attestator),
+            "reason": receipt.reason,
+        }),
+        None => json!({
+            "user_op_hash": user_op_hash,
+            "chain_id": network.chain_id,
+            "status": "pending",
+        }),
+    }))
+}
+
+pub async fn proxy_status(
+    AState(state): AState<State>,
+    Path(id): Path<String>,
+) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    for network in state.networks.values() {
+        if let Some(job) = network.relayer.job(&id).await {
+            return Ok(Json(job_response(&network.relayer, job)));
+        }
     }
-    .await
-    .map_err(|e| {
+
+    Err((
+        StatusCode::NOT_FOUND,
+        Json(json!({ "message": format!("Unknown relay job {}", id) })),
+    ))
+}
+
+fn job_response(pool: &RelayerPool, job: Job) -> Value {
+    let (from, to) = pool.inclusion_window(&job);
+    let mut response = json!(job);
+    response["inclusion_window"] = json!({ "from": from, "to": to });
+    response
+}
+
+pub async fn pow(AState(state): AState<State>) -> Json<Value> {
+    Json(json!({
+        "difficulty": state.gate.difficulty().await,
+        "pow_ttl": state.gate.config.pow_ttl,
+        "domain": String::from_utf8_lossy(POW_DOMAIN),
+    }))
+}
+
+pub async fn relayer_status(AState(state): AState<State>) -> Json<Value> {
+    let mut relayers = vec![];
+    for network in state.networks.values() {
+        let pool = &network.relayer;
+        let mut wallets = vec![];
+        for relayer in &pool.relayers {
+            wallets.push(json!({
+                "address": relayer.address,
+                "balance": relayer.guard.balance().await,
+                "low_balance": relayer.guard.is_low().await,
+                "ready": relayer.ready().await.is_ok(),
+                "in_flight": relayer.in_flight().await,
+                "health": relayer.health().await,
+            }));
+        }
+        relayers.push(json!({
+            "chain_id": network.chain_id,
+            "ready": pool.pick().await.is_ok(),
+            "spent_today": U256::from(pool.spent_today().await),
+            "daily_budget": pool.limits.daily_budget.map(U256::from),
+            "mixing": pool.config.mixing,
+            "rebalance": pool.config.rebalance,
+            "wallets": wallets,
+        }));
+    }
+
+    Json(json!({ "relayers": relayers }))
+}
+
+/// Degraded while a network's AnonymousAttester disagrees with the server.
+pub async fn health(AState(state): AState<State>) -> (StatusCode, Json<Value>) {
+    let degraded = state
+        .networks
+        .values()
+        .any(|network| !network.issues.is_empty());
+    let networks = state
+        .networks
+        .values()
+        .map(|network| {
+            json!({
+                "chain_id": network.chain_id,
+                "anonymous_attestator": network.anonymous_attestator,
+                "issues": network.issues,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    if degraded {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "status": "degraded", "networks": networks })),
+        )
+    } else {
+        (
+            StatusCode::OK,
+            Json(json!({ "status": "ok", "networks": networks })),
+        )
+    }
+}
+
+pub async fn nonce(AState(state): AState<State>) -> Json<Value> {
+    let (nonce, expires_at) = state.challenges.issue().await;
+
+    Json(json!({
+        "nonce": nonce,
+        "domain": state.challenges.domain,
+        "expires_at": expires_at,
+    }))
+}
+
+pub async fn anonymity(
+    AState(state): AState<State>,
+    Query(AnonymityQuery {
+        chain_id,
+        timestamp,
+    }): Query<AnonymityQuery>,
+) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    let network = state.network(chain_id).map_err(|e| {
         (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({ "message": format!("Failed to send transaction: {}", e) })),
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "message": format!("Unsupported network: {}", e) })),
         )
     })?;
+    let epoch = state.issuance.epoch(timestamp.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }));
+    let stats = state.issuance.stats(network.chain_id, epoch).await;
+    let roles = network
+        .roles
+        .providers()
+        .iter()
+        .flat_map(|role| {
+            let live = Some(role.role());
+            // signatures for the snapshots of the epoch, when there are any
+            let snapshot = Some(Snapshot::role(role.role())).filter(|r| stats.contains_key(r));
+            live.into_iter().chain(snapshot).map(|r| {
+                let stats = stats.get(&r).copied().unwrap_or_default();
+                json!({
+                    "role": r,
+                    "role_str": role.label(),
+                    "snapshot": r != role.role(),
+                    "signatures": stats.signatures,
+                    "addresses": stats.addresses,
+                })
+            })
+        })
+        .collect::<Vec<_>>();
 
-    Ok(Json(json!({ "tx_hash": tx_hash })))
+    Ok(Json(json!({
+        "chain_id": network.chain_id,
+        "epoch": epoch,
+        "epoch_seconds": state.issuance.epoch_seconds,
+        "roles": roles,
+    })))
 }
 
 pub async fn signature(
     AState(state): AState<State>,
-    Json(SignatureBody { signature, address }): Json<SignatureBody>,
+    Json(SignatureBody {
+        signature,
+        address,
+        message,
+        chain_id,
+        snapshot,
+    }): Json<SignatureBody>,
 ) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
-    let provider = state.provider;
-    let message = format!("CURIA VERIFY ACCOUNT OWNERSHIP {}", address);
+    let network = state.network(chain_id).map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "message": format!("Unsupported network: {}", e) })),
+        )
+    })?;
+    let provider = network.provider.clone();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    let siwe = message.parse::<SiweMessage>().map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "message": format!("Malformed SIWE message: {}", e) })),
+        )
+    })?;
+    siwe.validate(
+        &state.challenges.domain,
+        network.chain_id,
+        address,
+        state.challenges.ttl,
+        now,
+    )
+    .map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "message": format!("Invalid SIWE message: {}", e) })),
+        )
+    })?;
 
     if !match signature {
         Signature::ECDSA { r, s, v } => {
@@ -118,28 +424,43 @@ pub async fn signature(
                 y: ark_ed_on_bn254::Fq::from_be_bytes_mod_order(&r[32..]),
             };
             let s = ark_ed_on_bn254::Fr::from_be_bytes_mod_order(&s);
-            let registry = KeyRegistry::new(state.pubkey_registry, provider.clone());
-            let key = registry.key(address).call().await.map_err(|e| {
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({ "message": format!("Failed to get key: {}", e) })),
-                )
-            })?;
-
-            if key._0.x == FixedBytes::ZERO && key._0.y == FixedBytes::ZERO {
-                return Err((
-                    StatusCode::BAD_REQUEST,
-                    Json(json!({ "message": "Invalid public key" })),
-                ));
-            }
+            let registry = RegistryClient::new(network.pubkey_registry, provider.clone());
+            let key = registry
+                .key(address)
+                .await
+                .map_err(|e| {
+                    (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        Json(json!({ "message": format!("Failed to get key: {}", e) })),
+                    )
+                })?
+                .ok_or_else(|| {
+                    (
+                        StatusCode::BAD_REQUEST,
+                        Json(json!({ "message": "Invalid public key" })),
+                    )
+                })?;
 
             let pubkey = EdAffine {
-                x: ark_ed_on_bn254::Fq::from_be_bytes_mod_order(key._0.x.as_ref()),
-                y: ark_ed_on_bn254::Fq::from_be_bytes_mod_order(key._0.y.as_ref()),
+                x: ark_ed_on_bn254::Fq::from_be_bytes_mod_order(key.x.as_ref()),
+                y: ark_ed_on_bn254::Fq::from_be_bytes_mod_order(key.y.as_ref()),
             };
 
             eddsa_verify_message(pubkey, message.as_bytes(), r, s).unwrap_or_default()
         }
+        Signature::EIP1271 { signature } => verify_account_signature(
+            provider.clone(),
+            address,
+            message.as_bytes(),
+            &signature,
+        )
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "message": format!("Failed to verify wallet signature: {}", e) })),
+            )
+        })?,
     } {
         return Err((
             StatusCode::BAD_REQUEST,
@@ -149,40 +470,124 @@ pub async fn signature(
         ));
     }
 
-    let now = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_secs();
-
-    let signatures = ALL_ROLES
-        .into_iter()
-        .zip(
-            try_join_all(
-                ALL_ROLES.map(|role| state.querier.is_role(provider.clone(), address, role)),
+    let snapshot = match snapshot {
+        Some(reference) => Some(Snapshot::resolve(&provider, reference).await.map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "message": format!("Invalid snapshot: {}", e) })),
             )
-            .await
-            .map_err(|e| {
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({ "message": format!("Failed to query role: {}", e) })),
-                )
-            })?,
+        })?),
+        None => None,
+    };
+
+    state.challenges.consume(&siwe.nonce).await.map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "message": format!("Invalid nonce: {}", e) })),
         )
-        .filter(|(_, i)| *i)
-        .map(|(role, _)| -> Result<Value> {
-            let role_u8 = role as u8;
+    })?;
+
+    let epoch = state.issuance.epoch(now);
+    // a snapshot's time is signed in place of the epoch, with the snapshot
+    // role, so proofs show it and cannot pass for current ones
+    let timestamp = snapshot.map_or(epoch, |s| s.timestamp);
+    let eligibility = network.roles.eligible(address, snapshot).await;
+    // roles without history, not checked at the snapshot
+    let latest_only: Vec<String> = match snapshot {
+        Some(_) => network
+            .roles
+            .providers()
+            .iter()
+            .filter(|role| !role.supports_snapshots())
+            .map(|role| role.label().to_string())
+            .collect(),
+        None => vec![],
+    };
+    let unavailable = eligibility
+        .unavailable
+        .iter()
+        .map(|role| role.label().to_string())
+        .collect::<Vec<_>>();
+    if eligibility.roles.is_empty() && !unavailable.is_empty() {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({
+                "message": format!("Failed to query roles {:?}", unavailable),
+                "unavailable": unavailable,
+            })),
+        ));
+    }
+
+    let mut issued = vec![];
+    let mut exhausted = vec![];
+    for role in eligibility.roles {
+        // a snapshot has its own quota, which does not renew with the epochs
+        let key = match snapshot {
+            Some(snapshot) => EpochKey {
+                chain_id: network.chain_id,
+                epoch: snapshot.timestamp,
+                role: Snapshot::role(role.role()),
+            },
+            None => EpochKey {
+                chain_id: network.chain_id,
+                epoch,
+                role: role.role(),
+            },
+        };
+        let quota = state.policy.quota(role.role());
+        match state.issuance.issue(key, address, quota).await {
+            Ok(true) => issued.push(role),
+            Ok(false) => exhausted.push(role.label().to_string()),
+            Err(e) => {
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(json!({ "message": format!("Failed to record issuance: {}", e) })),
+                ))
+            }
+        }
+    }
+
+    if issued.is_empty() && !exhausted.is_empty() {
+        // the quota of a snapshot never renews
+        let retry_at = snapshot
+            .is_none()
+            .then_some(epoch + state.issuance.epoch_seconds);
+        return Err((
+            StatusCode::TOO_MANY_REQUESTS,
+            Json(json!({
+                "message": match retry_at {
+                    Some(retry_at) => format!(
+                        "Issuance quota exhausted for {:?} until {}",
+                        exhausted, retry_at
+                    ),
+                    None => format!("Issuance quota exhausted for {:?} at this snapshot", exhausted),
+                },
+                "exhausted": exhausted,
+                "retry_at": retry_at,
+            })),
+        ));
+    }
+
+    let signatures = issued
+        .iter()
+        .map(|role| -> Result<Value> {
+            let role_u8 = match snapshot {
+                Some(_) => Snapshot::role(role.role()),
+                None => role.role(),
+            };
             let random_nonce = ark_ed_on_bn254::Fq::rand(&mut OsRng);
-            let identity = hash(&[
-                ark_ed_on_bn254::Fq::from_be_bytes_mod_order(address.as_ref()),
-                ark_ed_on_bn254::Fq::from(role_u8),
-                ark_ed_on_bn254::Fq::from(now),
-                random_nonce
-            ])?;
+            let identity = Identity {
+                address,
+                role: role_u8,
+                timestamp,
+                random_nonce,
+            }
+            .hash()?;
             let signature = eddsa_sign(state.private_key, identity)?;
 
             Ok(json!({
                 "role": role_u8,
-                "role_str": role,
+                "role_str": role.label(),
                 "sig_rx": format!("0x{}", hex::encode(signature.0.x.into_bigint().to_bytes_be())),
                 "sig_ry": format!("0x{}", hex::encode(signature.0.y.into_bigint().to_bytes_be())),
                 "sig_s": format!("0x{}", hex::encode(signature.1.into_bigint().to_bytes_be())),
@@ -197,8 +602,20 @@ pub async fn signature(
             )
         })?;
 
+    let pubkey = (EdAffine::generator() * state.private_key).into_affine();
+
     Ok(Json(json!({
         "signatures": signatures,
-        "timestamp": now,
+        "exhausted": exhausted,
+        "unavailable": unavailable,
+        "latest_only": latest_only,
+        "timestamp": timestamp,
+        "snapshot": snapshot,
+        "chain_id": network.chain_id,
+        "attester": network.anonymous_attestator,
+        "pubkey": {
+            "x": format!("0x{}", hex::encode(pubkey.x.into_bigint().to_bytes_be())),
+            "y": format!("0x{}", hex::encode(pubkey.y.into_bigint().to_bytes_be())),
+        },
     })))
 }
@@ -0,0 +1,357 @@
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use alloy::primitives::Address;
+use anyhow::{anyhow, bail, ensure, Result};
+use ark_std::rand::{rngs::OsRng, RngCore};
+use tokio::sync::Mutex;
+
+const PREAMBLE: &str = " wants you to sign in with your Ethereum account:";
+
+/// Allowed clock drift between the client and the server, in seconds.
+const CLOCK_SKEW: u64 = 60;
+
+/// EIP-4361 (Sign-In with Ethereum) message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SiweMessage {
+    pub domain: String,
+    pub address: Address,
+    pub statement: Option<String>,
+    pub uri: String,
+    pub version: String,
+    pub chain_id: u64,
+    pub nonce: String,
+    pub issued_at: u64,
+    pub expiration_time: Option<u64>,
+    pub not_before: Option<u64>,
+    pub request_id: Option<String>,
+    pub resources: Vec<String>,
+}
+
+impl FromStr for SiweMessage {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut lines = s.lines().peekable();
+
+        let domain = lines
+            .next()
+            .and_then(|l| l.strip_suffix(PREAMBLE))
+            .filter(|d| !d.is_empty())
+            .ok_or_else(|| anyhow!("Missing preamble"))?
+            .to_string();
+        let address = lines
+            .next()
+            .ok_or_else(|| anyhow!("Missing address"))?
+            .parse::<Address>()
+            .map_err(|e| anyhow!("Invalid address: {}", e))?;
+        ensure!(lines.next() == Some(""), "Missing empty line after address");
+
+        let statement = match lines.peek() {
+            Some(l) if !l.starts_with("URI: ") && !l.is_empty() => {
+                let statement = l.to_string();
+                lines.next();
+                ensure!(
+                    lines.next() == Some(""),
+                    "Missing empty line after statement"
+                );
+                Some(statement)
+            }
+            Some(&"") => {
+                lines.next();
+                None
+            }
+            _ => None,
+        };
+
+        let mut field = |tag: &str| -> Result<String> {
+            lines
+                .next()
+                .and_then(|l| l.strip_prefix(tag))
+                .map(str::to_string)
+                .ok_or_else(|| anyhow!("Missing {}", tag.trim_end_matches(": ")))
+        };
+        let uri = field("URI: ")?;
+        let version = field("Version: ")?;
+        let chain_id = field("Chain ID: ")?
+            .parse()
+            .map_err(|_| anyhow!("Invalid chain ID"))?;
+        let nonce = field("Nonce: ")?;
+        let issued_at = parse_rfc3339(&field("Issued At: ")?)?;
+
+        let mut optional = |tag: &str| {
+            lines
+                .next_if(|l| l.starts_with(tag))
+                .map(|l| l[tag.len()..].to_string())
+        };
+        let expiration_time = optional("Expiration Time: ")
+            .map(|t| parse_rfc3339(&t))
+            .transpose()?;
+        let not_before = optional("Not Before: ")
+            .map(|t| parse_rfc3339(&t))
+            .transpose()?;
+        let request_id = optional("Request ID: ");
+        let mut resources = vec![];
+        if lines.next_if_eq(&"Resources:").is_some() {
+            while let Some(resource) = lines.next_if(|l| l.starts_with("- ")) {
+                resources.push(resource[2..].to_string());
+            }
+        }
+        ensure!(lines.next().is_none(), "Unexpected trailing content");
+
+        Ok(SiweMessage {
+            domain,
+            address,
+            statement,
+            uri,
+            version,
+            chain_id,
+            nonce,
+            issued_at,
+            expiration_time,
+            not_before,
+            request_id,
+            resources,
+        })
+    }
+}
+
+impl SiweMessage {
+    /// Check every field of the message against what the server expects.
+    /// The nonce is only checked for its format, it is consumed separately
+    /// by [`Challenges::consume`].
+    pub fn validate(
+        &self,
+        domain: &str,
+        chain_id: u64,
+        address: Address,
+        ttl: u64,
+        now: u64,
+    ) -> Result<()> {
+        ensure!(self.domain == domain, "Invalid domain {}", self.domain);
+        ensure!(self.address == address, "Address mismatch");
+        ensure!(!self.uri.is_empty(), "Missing URI");
+        ensure!(self.version == "1", "Unsupported version {}", self.version);
+        ensure!(
+            self.chain_id == chain_id,
+            "Invalid chain ID {}",
+            self.chain_id
+        );
+        ensure!(
+            self.nonce.len() >= 8 && self.nonce.chars().all(|c| c.is_ascii_alphanumeric()),
+            "Invalid nonce"
+        );
+        ensure!(
+            self.issued_at <= now + CLOCK_SKEW,
+            "Message issued in the future"
+        );
+        ensure!(self.issued_at + ttl >= now, "Message is too old");
+        if let Some(expiration_time) = self.expiration_time {
+            ensure!(expiration_time > now, "Message expired");
+        }
+        if let Some(not_before) = self.not_before {
+            ensure!(not_before <= now + CLOCK_SKEW, "Message not yet valid");
+        }
+        Ok(())
+    }
+}
+
+/// Server issued nonces for the ownership challenge, each usable only once.
+#[derive(Debug, Clone)]
+pub struct Challenges {
+    pub domain: String,
+    pub ttl: u64,
+    nonces: Arc<Mutex<HashMap<String, u64>>>,
+}
+
+impl Challenges {
+    pub fn new(domain: String, ttl: u64) -> Self {
+        Challenges {
+            domain,
+            ttl,
+            nonces: Default::default(),
+        }
+    }
+
+    /// Issue a new nonce, returns the nonce and its expiry.
+    pub async fn issue(&self) -> (String, u64) {
+        let mut bytes = [0u8; 16];
+        OsRng.fill_bytes(&mut bytes);
+        let nonce = hex::encode(bytes);
+        let now = now();
+        let expires_at = now + self.ttl;
+
+        let mut nonces = self.nonces.lock().await;
+        nonces.retain(|_, expiry| *expiry > now);
+        nonces.insert(nonce.clone(), expires_at);
+
+        (nonce, expires_at)
+    }
+
+    /// Consume a nonce, fails if it was never issued, already used or expired.
+    pub async fn consume(&self, nonce: &str) -> Result<()> {
+        match self.nonces.lock().await.remove(nonce) {
+            Some(expiry) if expiry > now() => Ok(()),
+            Some(_) => bail!("Nonce expired"),
+            None => bail!("Unknown or already used nonce"),
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// Parse an RFC 3339 date-time into a unix timestamp in seconds.
+pub fn parse_rfc3339(s: &str) -> Result<u64> {
+    let invalid = || anyhow!("Invalid timestamp {}", s);
+    let num = |r: std::ops::Range<usize>| -> Result<i64> {
+        s.get(r)
+            .filter(|d| d.bytes().all(|b| b.is_ascii_digit()))
+            .ok_or_else(invalid)?
+            .parse()
+            .map_err(|_| invalid())
+    };
+    let b = s.as_bytes();
+    ensure!(
+        b.len() >= 20
+            && b[4] == b'-'
+            && b[7] == b'-'
+            && matches!(b[10], b'T' | b't')
+            && b[13] == b':'
+            && b[16] == b':',
+        "Invalid timestamp {}",
+        s
+    );
+    let (year, month, day) = (num(0..4)?, num(5..7)?, num(8..10)?);
+    let (hour, minute, second) = (num(11..13)?, num(14..16)?, num(17..19)?);
+    ensure!(
+        (1..=12).contains(&month)
+            && (1..=31).contains(&day)
+            && hour < 24
+            && minute < 60
+            && second <= 60,
+        "Invalid timestamp {}",
+        s
+    );
+
+    let mut rest = &s[19..];
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let digits = fraction.bytes().take_while(u8::is_ascii_digit).count();
+        ensure!(digits > 0, "Invalid timestamp {}", s);
+        rest = &fraction[digits..];
+    }
+    let offset = match rest.as_bytes() {
+        [b'Z' | b'z'] => 0,
+        [sign @ (b'+' | b'-'), _, _, b':', _, _] => {
+            let offset = num(s.len() - 5..s.len() - 3)? * 3600 + num(s.len() - 2..s.len())? * 60;
+            if *sign == b'+' {
+                offset
+            } else {
+                -offset
+            }
+        }
+        _ => return Err(invalid()),
+    };
+
+    // days from civil, see http://howardhinnant.github.io/date_algorithms.html
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+
+    let timestamp = days * 86400 + hour * 3600 + minute * 60 + second - offset;
+    u64::try_from(timestamp).map_err(|_| invalid())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::address;
+    use anyhow::Result;
+
+    use super::*;
+
+    const MESSAGE: &str = "curia.example wants you to sign in with your Ethereum account:
+0x000000000000000000000000000000000000dEaD
+
+Verify account ownership
+
+URI: https://curia.example
+Version: 1
+Chain ID: 10
+Nonce: 32891756abcdef12
+Issued At: 2024-06-20T09:30:52Z
+Expiration Time: 2024-06-20T09:40:52.000Z
+Resources:
+- https://curia.example/signature";
+
+    #[test]
+    fn parse_siwe_message() -> Result<()> {
+        let message = MESSAGE.parse::<SiweMessage>()?;
+        assert_eq!(message.domain, "curia.example");
+        assert_eq!(
+            message.address,
+            address!("000000000000000000000000000000000000dEaD")
+        );
+        assert_eq!(
+            message.statement.as_deref(),
+            Some("Verify account ownership")
+        );
+        assert_eq!(message.chain_id, 10);
+        assert_eq!(message.nonce, "32891756abcdef12");
+        assert_eq!(message.issued_at, 1718875852);
+        assert_eq!(message.expiration_time, Some(1718876452));
+        assert_eq!(message.resources, vec!["https://curia.example/signature"]);
+
+        let without_statement = MESSAGE.replace("Verify account ownership\n\n", "");
+        assert_eq!(without_statement.parse::<SiweMessage>()?.statement, None);
+        Ok(())
+    }
+
+    #[test]
+    fn validate_siwe_message() -> Result<()> {
+        let message = MESSAGE.parse::<SiweMessage>()?;
+        let address = address!("000000000000000000000000000000000000dEaD");
+        let now = 1718875900;
+
+        assert!(message
+            .validate("curia.example", 10, address, 300, now)
+            .is_ok());
+        assert!(message
+            .validate("evil.example", 10, address, 300, now)
+            .is_err());
+        assert!(message
+            .validate("curia.example", 11155420, address, 300, now)
+            .is_err());
+        assert!(message
+            .validate("curia.example", 10, Address::ZERO, 300, now)
+            .is_err());
+        assert!(message
+            .validate("curia.example", 10, address, 300, now + 600)
+            .is_err());
+        assert!(message
+            .validate("curia.example", 10, address, 300, now - 3600)
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn parse_rfc3339_offsets() -> Result<()> {
+        assert_eq!(parse_rfc3339("1970-01-01T00:00:00Z")?, 0);
+        assert_eq!(parse_rfc3339("2024-06-20T11:30:52+02:00")?, 1718875852);
+        assert_eq!(parse_rfc3339("2024-06-20T09:30:52.123456Z")?, 1718875852);
+        assert!(parse_rfc3339("2024-06-20 09:30:52").is_err());
+        assert!(parse_rfc3339("2024-13-20T09:30:52Z").is_err());
+        Ok(())
+    }
+}
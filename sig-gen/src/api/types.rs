@@ -1,84 +1,110 @@
+use std::{collections::HashMap, sync::Arc};
+
 use alloy::{
-    primitives::{Address, Bytes, B256},
+    primitives::{Address, Bytes},
     providers::ReqwestProvider,
-    sol,
 };
-use anyhow::{bail, Result};
+use anyhow::{anyhow, Result};
 use ark_ed_on_bn254::Fr as EdFr;
 use serde::Deserialize;
 
-use crate::query::RoleQuerier;
+use super::Challenges;
+use crate::{
+    contracts::Inconsistency,
+    issuance::Issuance,
+    policy::Policy,
+    query::{RoleRegistry, SnapshotRef},
+    relayer::{Gate, ProofOfWork, RelayerPool, UserOpRelayer},
+};
 
 #[derive(Debug, Clone)]
 pub struct State {
-    pub provider: ReqwestProvider,
-    pub testnet_provider: Option<ReqwestProvider>,
-    pub querier: RoleQuerier,
+    pub networks: Arc<HashMap<u64, Network>>,
+    pub default_chain_id: u64,
     pub private_key: EdFr,
-    pub pubkey_registry: Address,
-    pub testnet_pubkey_registry: Option<Address>,
-    pub anonymous_attestator: Address,
-    pub testnet_anonymous_attestator: Option<Address>,
-    pub proxy_private_key: B256,
+    pub challenges: Challenges,
+    pub issuance: Issuance,
+    pub policy: Policy,
+    pub gate: Gate,
 }
 
 impl State {
-    pub fn provider(&self, is_testnet: Option<bool>) -> Result<&ReqwestProvider> {
-        match (is_testnet, self.testnet_provider.as_ref()) {
-            (Some(true), Some(p)) => Ok(p),
-            (Some(true), None) => {
-                bail!("Testnet provider not set");
-            }
-            _ => Ok(&self.provider),
-        }
-    }
-
-    pub fn pubkey_registry(&self, is_testnet: Option<bool>) -> Result<Address> {
-        match (is_testnet, self.testnet_pubkey_registry) {
-            (Some(true), Some(addr)) => Ok(addr),
-            (Some(true), None) => {
-                bail!("Testnet pubkey registry not set");
-            }
-            _ => Ok(self.pubkey_registry),
-        }
+    pub fn network(&self, chain_id: Option<u64>) -> Result<&Network> {
+        let chain_id = chain_id.unwrap_or(self.default_chain_id);
+        self.networks
+            .get(&chain_id)
+            .ok_or_else(|| anyhow!("Network {} not configured", chain_id))
     }
+}
 
-    pub fn anonymous_attestator(&self, is_testnet: Option<bool>) -> Result<Address> {
-        match (is_testnet, self.testnet_anonymous_attestator) {
-            (Some(true), Some(addr)) => Ok(addr),
-            (Some(true), None) => {
-                bail!("Testnet anonymous attestator not set");
-            }
-            _ => Ok(self.anonymous_attestator),
-        }
-    }
+#[derive(Debug, Clone)]
+pub struct Network {
+    pub chain_id: u64,
+    pub provider: ReqwestProvider,
+    pub roles: RoleRegistry,
+    pub relayer: RelayerPool,
+    pub user_ops: Option<UserOpRelayer>,
+    pub pubkey_registry: Address,
+    pub anonymous_attestator: Address,
+    /// Whether the attester's verifier is the one of `verify_proof`, so
+    /// proofs are checked natively before relaying rather than only
+    /// simulated.
+    pub native_proofs: bool,
+    /// Mismatches with the AnonymousAttester found at startup.
+    pub issues: Vec<Inconsistency>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ProxyTransactionRequest {
     pub input: Bytes,
-    pub is_testnet: Option<bool>,
+    pub chain_id: Option<u64>,
+    /// Hold the call in the mixing pool before it is broadcast.
+    #[serde(default)]
+    pub mix: bool,
+    pub pow: Option<ProofOfWork>,
+    /// Relay as a sponsored ERC-4337 user operation instead of a transaction
+    /// of a relayer wallet.
+    #[serde(default)]
+    pub user_op: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChainQuery {
+    pub chain_id: Option<u64>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct SignatureBody {
     pub signature: Signature,
     pub address: Address,
+    /// EIP-4361 message signed by `signature`, carrying a nonce from `/nonce`.
+    pub message: String,
+    pub chain_id: Option<u64>,
+    /// Check the roles at a past block instead of the latest one.
+    pub snapshot: Option<SnapshotRef>,
 }
 
 #[derive(Debug, Deserialize)]
-pub enum Signature {
-    ECDSA { r: Bytes, s: Bytes, v: u8 },
-    EDDSA { r: Bytes, s: Bytes },
+pub struct AnonymityQuery {
+    pub chain_id: Option<u64>,
+    /// Any timestamp within the epoch, defaults to the current one.
+    pub timestamp: Option<u64>,
 }
 
-sol! {
-    #[sol(rpc)]
-    contract KeyRegistry {
-        struct Key {
-            bytes32 x;
-            bytes32 y;
-        }
-        function key(address addr) external view returns (Key memory);
-    }
+#[derive(Debug, Deserialize)]
+pub enum Signature {
+    ECDSA {
+        r: Bytes,
+        s: Bytes,
+        v: u8,
+    },
+    EDDSA {
+        r: Bytes,
+        s: Bytes,
+    },
+    /// Smart-contract wallet signature, checked with EIP-1271 and unwrapped
+    /// from EIP-6492 for accounts that are not deployed yet.
+    EIP1271 {
+        signature: Bytes,
+    },
 }
@@ -0,0 +1,244 @@
+use alloy::{
+    contract::Error as ContractError,
+    primitives::{address, b256, eip191_hash_message, Address, Bytes, FixedBytes, B256},
+    providers::{Provider, ReqwestProvider},
+    signers::Signature as AlloySignature,
+    sol,
+    sol_types::{Error as SolError, SolCall, SolType},
+};
+use anyhow::Result;
+
+use crate::contracts::revert_data;
+
+/// Suffix of an EIP-6492 wrapped signature.
+const ERC6492_MAGIC: B256 =
+    b256!("6492649264926492649264926492649264926492649264926492649264926492");
+
+/// `isValidSignature(bytes32,bytes)` selector, returned by EIP-1271 wallets on success.
+const ERC1271_MAGIC: FixedBytes<4> = FixedBytes([0x16, 0x26, 0xba, 0x7e]);
+
+/// Multicall3 is deployed at the same address on every OP Stack chain.
+const MULTICALL3_ADDRESS: Address = address!("cA11bde05977b3631167028862bE2a173976CA11");
+
+sol! {
+    #[sol(rpc)]
+    contract ERC1271 {
+        function isValidSignature(bytes32 hash, bytes signature) external view returns (bytes4);
+    }
+
+    #[sol(rpc)]
+    contract Multicall3 {
+        struct Call3 {
+            address target;
+            bool allowFailure;
+            bytes callData;
+        }
+        struct Call3Result {
+            bool success;
+            bytes returnData;
+        }
+        function aggregate3(Call3[] calldata calls) external payable returns (Call3Result[] memory returnData);
+    }
+
+    struct ERC6492Signature {
+        address factory;
+        bytes factoryCalldata;
+        bytes signature;
+    }
+}
+
+/// Verify a signature over the EIP-191 hash of `message` for any kind of account.
+///
+/// Deployed smart-contract wallets are checked with EIP-1271 `isValidSignature`,
+/// counterfactual ones through their EIP-6492 wrapper by deploying and checking
+/// in a single `eth_call`, and plain 65 bytes signatures of accounts without code
+/// fall back to ECDSA recovery.
+pub async fn verify_account_signature(
+    provider: ReqwestProvider,
+    address: Address,
+    message: &[u8],
+    signature: &Bytes,
+) -> Result<bool> {
+    let hash = eip191_hash_message(message);
+    let code = provider.get_code_at(address).await?;
+
+    match erc6492_wrapper(signature) {
+        Some(wrapper) => {
+            let Ok(wrapper) = wrapper else {
+                return Ok(false);
+            };
+
+            if !code.is_empty() {
+                return is_valid_signature(provider, address, hash, wrapper.signature).await;
+            }
+
+            // Deploy the account from its factory and validate right after, all
+            // inside the same simulated call so nothing is ever broadcast.
+            let multicall = Multicall3::new(MULTICALL3_ADDRESS, provider);
+            let results = multicall
+                .aggregate3(deploy_and_validate(address, hash, wrapper))
+                .call()
+                .await?
+                .returnData;
+
+            Ok(results.get(1).is_some_and(|result| {
+                result.success && result.returnData.starts_with(ERC1271_MAGIC.as_slice())
+            }))
+        }
+        None if !code.is_empty() => {
+            is_valid_signature(provider, address, hash, signature.clone()).await
+        }
+        None => Ok(AlloySignature::try_from(signature.as_ref())
+            .and_then(|sig| sig.recover_address_from_prehash(&hash))
+            .is_ok_and(|recovered| recovered == address)),
+    }
+}
+
+/// The EIP-6492 wrapper of `signature`, `None` when it lacks the magic suffix.
+fn erc6492_wrapper(signature: &[u8]) -> Option<Result<ERC6492Signature, SolError>> {
+    signature
+        .strip_suffix(ERC6492_MAGIC.as_slice())
+        .map(|wrapped| ERC6492Signature::abi_decode_sequence(wrapped, false))
+}
+
+/// Multicall3 calls deploying `address` from its factory, then checking the
+/// wrapped signature over `hash`.
+fn deploy_and_validate(
+    address: Address,
+    hash: B256,
+    wrapper: ERC6492Signature,
+) -> Vec<Multicall3::Call3> {
+    vec![
+        Multicall3::Call3 {
+            target: wrapper.factory,
+            allowFailure: true,
+            callData: wrapper.factoryCalldata,
+        },
+        Multicall3::Call3 {
+            target: address,
+            allowFailure: true,
+            callData: ERC1271::isValidSignatureCall {
+                hash,
+                signature: wrapper.signature,
+            }
+            .abi_encode()
+            .into(),
+        },
+    ]
+}
+
+/// RPC failures are errors, while a revert carrying its data or an
+/// undecodable return value means the wallet refused the signature.
+async fn is_valid_signature(
+    provider: ReqwestProvider,
+    address: Address,
+    hash: B256,
+    signature: Bytes,
+) -> Result<bool> {
+    match ERC1271::new(address, provider)
+        .isValidSignature(hash, signature)
+        .call()
+        .await
+    {
+        Ok(result) => Ok(result._0 == ERC1271_MAGIC),
+        Err(ContractError::TransportError(e)) if revert_data(&e).is_some() => Ok(false),
+        Err(ContractError::AbiError(_)) => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::{
+        primitives::{bytes, keccak256},
+        providers::ProviderBuilder,
+    };
+    use axum::{routing::post, Json, Router};
+    use reqwest::Url;
+    use serde_json::{json, Value};
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    /// Node standing in for a real one: the wallet accepts `c0ffee`, reverts
+    /// on `bad5e9`, and any other signature fails the call without a revert.
+    async fn node(Json(request): Json<Value>) -> Json<Value> {
+        let id = request["id"].clone();
+        let data: Bytes = serde_json::from_value(request["params"][0]["input"].clone())
+            .or_else(|_| serde_json::from_value(request["params"][0]["data"].clone()))
+            .unwrap();
+        let call = ERC1271::isValidSignatureCall::abi_decode(&data, true).unwrap();
+        let valid = ERC1271::isValidSignatureCall::abi_encode_returns(&(ERC1271_MAGIC,));
+        Json(match call.signature.as_ref() {
+            [0xc0, 0xff, 0xee] => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": Bytes::from(valid),
+            }),
+            [0xba, 0xd5, 0xe9] => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": 3, "message": "execution reverted", "data": "0x" },
+            }),
+            _ => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": -32000, "message": "header not found" },
+            }),
+        })
+    }
+
+    #[tokio::test]
+    async fn refusals_and_rpc_failures() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = Url::parse(&format!("http://{}", listener.local_addr()?))?;
+        let app = Router::new().route("/", post(node));
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let provider = ProviderBuilder::new().on_http(url);
+        let wallet = address!("000000000000000000000000000000000000a11c");
+        let hash = keccak256("message");
+        let check = |signature| is_valid_signature(provider.clone(), wallet, hash, signature);
+
+        assert!(check(bytes!("c0ffee")).await?);
+        assert!(!check(bytes!("bad5e9")).await?);
+        assert!(check(bytes!("deadbeef")).await.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn erc6492_calls() {
+        let wallet = address!("000000000000000000000000000000000000a11c");
+        let factory = address!("0000000000000000000000000000000000fac704");
+        let hash = keccak256("message");
+        let wrapper = ERC6492Signature {
+            factory,
+            factoryCalldata: bytes!("deadbeef"),
+            signature: bytes!("c0ffee"),
+        };
+
+        let mut wrapped = ERC6492Signature::abi_encode_sequence(&wrapper);
+        assert!(erc6492_wrapper(&wrapped).is_none());
+        wrapped.extend_from_slice(ERC6492_MAGIC.as_slice());
+        let unwrapped = erc6492_wrapper(&wrapped).unwrap().unwrap();
+        assert_eq!(unwrapped.factory, factory);
+        assert_eq!(unwrapped.factoryCalldata, wrapper.factoryCalldata);
+        assert_eq!(unwrapped.signature, wrapper.signature);
+        assert!(erc6492_wrapper(ERC6492_MAGIC.as_slice()).unwrap().is_err());
+
+        let calls = deploy_and_validate(wallet, hash, unwrapped);
+        let calldata = Multicall3::aggregate3Call { calls }.abi_encode();
+        assert_eq!(calldata[..4], Multicall3::aggregate3Call::SELECTOR);
+        let calls = Multicall3::aggregate3Call::abi_decode(&calldata, true)
+            .unwrap()
+            .calls;
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].target, factory);
+        assert_eq!(calls[0].callData, bytes!("deadbeef"));
+        assert_eq!(calls[1].target, wallet);
+        assert!(calls.iter().all(|call| call.allowFailure));
+        let check = ERC1271::isValidSignatureCall::abi_decode(&calls[1].callData, true).unwrap();
+        assert_eq!(check.hash, hash);
+        assert_eq!(check.signature, bytes!("c0ffee"));
+    }
+}
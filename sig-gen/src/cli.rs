@@ -0,0 +1,248 @@
+use std::{collections::HashMap, env::var, fmt::Debug, fs, path::PathBuf};
+
+use alloy::{
+    primitives::{Bytes, B256},
+    providers::{network::EthereumWallet, ProviderBuilder},
+    signers::local::PrivateKeySigner,
+    sol_types::SolCall,
+};
+use anyhow::{anyhow, bail, Result};
+use ark_ec::{AffineRepr, CurveGroup};
+use ark_ed_on_bn254::Fr as EdFr;
+use ark_ff::{BigInteger, PrimeField};
+use reqwest::Url;
+use sig_gen::{
+    config::{Config, NetworkConfig},
+    contracts::{AnonymousAttester, AttesterClient},
+    crypto::{
+        parse_field, pubkey_words, verify_proof, EdAffine, PublicInputs, SignatureResponse, Witness,
+    },
+};
+
+/// Run `sig-gen <command> --flag value ...` instead of the server.
+pub async fn run(command: &str, args: &[String]) -> Result<()> {
+    match command {
+        "witness" => witness(&Flags::parse(args)?),
+        "verify" => verify(&Flags::parse(args)?),
+        "admin" => match args.split_first() {
+            Some((action, args)) => admin(action, &Flags::parse(args)?).await,
+            None => bail!("Missing admin action, expected {}", ADMIN_ACTIONS),
+        },
+        _ => bail!(
+            "Unknown command {}, expected witness, verify or admin",
+            command
+        ),
+    }
+}
+
+struct Flags(HashMap<String, String>);
+
+impl Flags {
+    fn parse(args: &[String]) -> Result<Self> {
+        let mut flags = HashMap::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let name = arg
+                .strip_prefix("--")
+                .ok_or_else(|| anyhow!("Unexpected argument {}", arg))?;
+            let value = args
+                .next()
+                .ok_or_else(|| anyhow!("Missing value of --{}", name))?;
+            flags.insert(name.to_string(), value.clone());
+        }
+        Ok(Flags(flags))
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.0.get(name).map(String::as_str)
+    }
+
+    fn required(&self, name: &str) -> Result<&str> {
+        self.get(name).ok_or_else(|| anyhow!("Missing --{}", name))
+    }
+}
+
+/// `witness --signature <response.json> --address <address> --secret <field>
+/// --message <text> --nonce <field> [--title <text>] [--role <role>] [--pubkey <x>,<y>]
+/// [--out <dir>]`
+///
+/// Write the `Prover.toml` and `Verifier.toml` of a `/signature` response.
+fn witness(flags: &Flags) -> Result<()> {
+    let response: SignatureResponse =
+        serde_json::from_str(&fs::read_to_string(flags.required("signature")?)?)?;
+    let role = match flags.get("role") {
+        Some(role) => role.parse()?,
+        None => match response.signatures.as_slice() {
+            [signature] => signature.role,
+            _ => bail!("Pick one of the signatures with --role"),
+        },
+    };
+    let pubkey = match flags.get("pubkey") {
+        Some(pubkey) => parse_pubkey(pubkey)?,
+        None => response
+            .pubkey
+            .ok_or_else(|| anyhow!("The response has no public key, pass --pubkey"))?
+            .into(),
+    };
+
+    let witness = Witness::new(
+        &response,
+        role,
+        flags.required("address")?.parse()?,
+        pubkey,
+        parse_field(flags.required("secret")?)?,
+        [
+            flags.get("title").unwrap_or_default(),
+            flags.required("message")?,
+        ]
+        .concat()
+        .as_bytes(),
+        parse_field(flags.required("nonce")?)?,
+    )?;
+    let out = PathBuf::from(flags.get("out").unwrap_or("."));
+    fs::write(out.join("Prover.toml"), witness.prover_toml())?;
+    fs::write(out.join("Verifier.toml"), witness.verifier_toml()?)?;
+
+    println!(
+        "revoker_hash: 0x{}",
+        hex::encode(witness.revoker_hash()?.into_bigint().to_bytes_be())
+    );
+    Ok(())
+}
+
+/// `verify --calldata <hex> --pubkey <x>,<y>` or `verify --proof <file>
+/// --inputs <word>,...`
+///
+/// Check an attestation proof against the circuit's verification key, given
+/// the `attest` calldata and the `curiaPubkey` it was sent under, or a proof
+/// of `nargo prove` and its seven public inputs.
+fn verify(flags: &Flags) -> Result<()> {
+    let (proof, inputs) = match flags.get("calldata") {
+        Some(calldata) => {
+            let call =
+                AnonymousAttester::attestCall::abi_decode(&calldata.parse::<Bytes>()?, true)?;
+            let pubkey = pubkey_words(&parse_pubkey(flags.required("pubkey")?)?);
+            let inputs = PublicInputs::from_call(pubkey, &call).to_array();
+            (call.proof.proof.to_vec(), inputs.to_vec())
+        }
+        None => (
+            hex::decode(fs::read_to_string(flags.required("proof")?)?.trim())?,
+            flags
+                .required("inputs")?
+                .split(',')
+                .map(|input| Ok(input.parse::<B256>()?))
+                .collect::<Result<Vec<_>>>()?,
+        ),
+    };
+
+    verify_proof(&proof, &inputs)?;
+    println!("valid");
+    Ok(())
+}
+
+const ADMIN_ACTIONS: &str = "change-pubkey, change-verifier, add-schema or remove-schema";
+
+/// `admin <action> [--chain-id <id>] [--rpc <url>] [--attester <address>]
+/// [--owner-key <hex>]`, the action being `change-pubkey [--pubkey <x>,<y>]`,
+/// `change-verifier --verifier <address>`, `add-schema --schema <uid>` or
+/// `remove-schema --schema <uid>`.
+///
+/// Print an owner call to the AnonymousAttester of a configured network and
+/// simulate it from the owner. It is only sent given the owner's key.
+async fn admin(action: &str, flags: &Flags) -> Result<()> {
+    match action {
+        "change-pubkey" => {
+            let pubkey = match flags.get("pubkey") {
+                Some(pubkey) => parse_pubkey(pubkey)?,
+                None => {
+                    let private_key =
+                        EdFr::from_be_bytes_mod_order(&hex::decode(var("PRIVATE_KEY")?)?);
+                    (EdAffine::generator() * private_key).into_affine()
+                }
+            };
+            let call = AnonymousAttester::changePubkeyCall {
+                pubkey: pubkey_words(&pubkey),
+            };
+            owner_call(call, flags).await
+        }
+        "change-verifier" => {
+            let call = AnonymousAttester::changeVerifierCall {
+                verifier: flags.required("verifier")?.parse()?,
+            };
+            owner_call(call, flags).await
+        }
+        "add-schema" => {
+            let call = AnonymousAttester::addSchemaCall {
+                schema: flags.required("schema")?.parse()?,
+            };
+            owner_call(call, flags).await
+        }
+        "remove-schema" => {
+            let call = AnonymousAttester::removeSchemaCall {
+                schema: flags.required("schema")?.parse()?,
+            };
+            owner_call(call, flags).await
+        }
+        _ => bail!(
+            "Unknown admin action {}, expected {}",
+            action,
+            ADMIN_ACTIONS
+        ),
+    }
+}
+
+async fn owner_call<C: SolCall + Debug>(call: C, flags: &Flags) -> Result<()> {
+    let rpc_url: Url = match flags.get("rpc") {
+        Some(rpc_url) => rpc_url.parse()?,
+        None => network(flags)?.rpc_url.parse()?,
+    };
+    let attester = match flags.get("attester") {
+        Some(attester) => attester.parse()?,
+        None => network(flags)?.anonymous_attestator,
+    };
+
+    let client = AttesterClient::new(attester, ProviderBuilder::new().on_http(rpc_url.clone()));
+    let owner = client.owner().await?;
+    println!("to: {}", attester);
+    println!("from: {} (owner)", owner);
+    println!("call: {:#?}", call);
+    println!("calldata: {}", Bytes::from(call.abi_encode()));
+    client.simulate(&call, owner).await?;
+    println!("simulation: ok");
+
+    let Some(owner_key) = flags.get("owner-key") else {
+        return Ok(());
+    };
+    let signer = PrivateKeySigner::from_bytes(&owner_key.parse()?)?;
+    if signer.address() != owner {
+        bail!("--owner-key is {}, not the owner", signer.address());
+    }
+    let provider = ProviderBuilder::new()
+        .with_recommended_fillers()
+        .wallet(EthereumWallet::new(signer))
+        .on_http(rpc_url);
+    let tx_hash = AttesterClient::new(attester, provider).send(&call).await?;
+    println!("sent: {}", tx_hash);
+    Ok(())
+}
+
+/// Network `--chain-id` of the config, the default one if unset.
+fn network(flags: &Flags) -> Result<NetworkConfig> {
+    let config = Config::load(var("CONFIG_PATH").unwrap_or("config.json".to_string()))?;
+    let chain_id = match flags.get("chain-id") {
+        Some(chain_id) => chain_id.parse()?,
+        None => config.default_chain_id,
+    };
+    config
+        .networks
+        .into_iter()
+        .find(|network| network.chain_id == chain_id)
+        .ok_or_else(|| anyhow!("Chain {} is not configured", chain_id))
+}
+
+fn parse_pubkey(pubkey: &str) -> Result<EdAffine> {
+    let (x, y) = pubkey
+        .split_once(',')
+        .ok_or_else(|| anyhow!("Expected --pubkey <x>,<y>"))?;
+    Ok(EdAffine::new_unchecked(parse_field(x)?, parse_field(y)?))
+}
@@ -0,0 +1,576 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use alloy::primitives::{address, b256, Address, B256, U256};
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+use crate::{
+    contracts::StartupCheck,
+    policy::Policy,
+    relayer::{Mixing, RelayError},
+};
+
+const OPTIMISM_TOKEN_ADDRESS: Address = address!("4200000000000000000000000000000000000042");
+pub const ENTRY_POINT_V07: Address = address!("0000000071727De22E5E9d8BAf0edAc6f37da032");
+
+/// Service configuration, loaded from the JSON file at `CONFIG_PATH`.
+/// Secrets stay in the environment.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Config {
+    /// Network used when a request does not specify a `chain_id`.
+    pub default_chain_id: u64,
+    pub networks: Vec<NetworkConfig>,
+    /// Granularity of signed timestamps, hourly buckets by default.
+    #[serde(default = "default_epoch_seconds")]
+    pub epoch_seconds: u64,
+    #[serde(default)]
+    pub policy: Policy,
+    /// Directory of the service's persistent state.
+    #[serde(default = "default_data_dir")]
+    pub data_dir: PathBuf,
+    #[serde(default)]
+    pub relayer: RelayerConfig,
+    #[serde(default)]
+    pub gate: GateConfig,
+    /// Handling of networks whose AnonymousAttester does not match the
+    /// server's key or schema.
+    #[serde(default)]
+    pub startup_check: StartupCheck,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NetworkConfig {
+    pub chain_id: u64,
+    pub rpc_url: String,
+    pub pubkey_registry: Address,
+    pub anonymous_attestator: Address,
+    /// EAS schema of the network's attestations, checked to be allowed by
+    /// the AnonymousAttester at startup.
+    pub schema: Option<B256>,
+    #[serde(default = "default_op_token")]
+    pub op_token: Address,
+    pub eas_graphql: String,
+    #[serde(default)]
+    pub spending: SpendingLimits,
+    /// Relaying through ERC-4337 user operations, disabled when unset.
+    pub user_ops: Option<UserOpConfig>,
+    /// Roles of token holders, signed next to the built-in roles.
+    #[serde(default)]
+    pub token_roles: Vec<TokenRoleConfig>,
+    /// Roles of the recipients of EAS attestations.
+    #[serde(default)]
+    pub attestation_roles: Vec<AttestationRoleConfig>,
+    /// Roles of delegates of `op_token` by voting power or rank.
+    #[serde(default)]
+    pub delegate_tiers: Vec<DelegateTierConfig>,
+    /// Scanning of the delegate index, required by `delegate_tiers` and
+    /// built only for them.
+    pub delegate_index: Option<DelegateIndexConfig>,
+}
+
+/// Role of the holders of an ERC-20, ERC-721 or ERC-1155 token.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct TokenRoleConfig {
+    pub id: String,
+    /// Role number signed in the identity, distinct from the other roles.
+    pub role: u8,
+    pub label: String,
+    /// Chain of the token, the network's own when unset.
+    pub chain_id: Option<u64>,
+    /// RPC of `chain_id`, required when it is not a configured network.
+    pub rpc_url: Option<String>,
+    pub contract: Address,
+    pub standard: TokenStandard,
+    #[serde(default = "default_min_balance")]
+    pub min_balance: U256,
+    /// Token to hold, required for ERC-1155. An ERC-721 role with a token ID
+    /// is only held by that token's owner.
+    pub token_id: Option<U256>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenStandard {
+    Erc20,
+    Erc721,
+    Erc1155,
+}
+
+/// Role of the recipients of attestations of an EAS schema.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct AttestationRoleConfig {
+    pub id: String,
+    /// Role number signed in the identity, distinct from the other roles.
+    pub role: u8,
+    pub label: String,
+    pub schema: B256,
+    /// Attesters whose attestations count, any attester when empty.
+    #[serde(default)]
+    pub attesters: Vec<Address>,
+    /// Condition on a field of the decoded attestation data.
+    pub predicate: Option<FieldPredicate>,
+    #[serde(default)]
+    pub revocation: RevocationPolicy,
+    /// EAS GraphQL endpoint, the network's `eas_graphql` when unset.
+    pub eas_graphql: Option<String>,
+    /// Interval between refreshes of the recipients.
+    #[serde(default = "default_refresh_seconds")]
+    pub refresh_seconds: u64,
+}
+
+/// `field` of the schema compared with `condition`. Values are compared as
+/// numbers when both parse as one, decimal or `0x` hex, and as text otherwise.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct FieldPredicate {
+    pub field: String,
+    #[serde(flatten)]
+    pub condition: Condition,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Condition {
+    Eq {
+        value: String,
+    },
+    Ne {
+        value: String,
+    },
+    In {
+        values: Vec<String>,
+    },
+    Gte {
+        value: String,
+    },
+    Lte {
+        value: String,
+    },
+    /// The greatest value of the field among the attestations, e.g. the
+    /// latest round.
+    Latest,
+}
+
+/// How revoked attestations count.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RevocationPolicy {
+    /// Only attestations that are not revoked grant the role.
+    #[default]
+    Exclude,
+    /// Revoked attestations grant the role as well.
+    Include,
+    /// A recipient with any revoked attestation does not get the role.
+    Strict,
+}
+
+impl AttestationRoleConfig {
+    /// Badgeholders of the latest RetroPGF round, attested by the Optimism
+    /// Foundation.
+    pub fn badgeholder() -> Self {
+        AttestationRoleConfig {
+            id: "badgeholder".to_string(),
+            role: 1,
+            label: "Badgeholder".to_string(),
+            schema: b256!("fdcfdad2dbe7489e0ce56b260348b7f14e8365a8a325aef9834818c00d46b31b"),
+            attesters: vec![
+                address!("621477dBA416E12df7FF0d48E14c4D20DC85D7D9"),
+                address!("E4553b743E74dA3424Ac51f8C1E586fd43aE226F"),
+            ],
+            predicate: Some(FieldPredicate {
+                field: "rpgfRound".to_string(),
+                condition: Condition::Latest,
+            }),
+            revocation: RevocationPolicy::Exclude,
+            eas_graphql: None,
+            refresh_seconds: default_refresh_seconds(),
+        }
+    }
+}
+
+impl TokenRoleConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.standard == TokenStandard::Erc1155 && self.token_id.is_none() {
+            bail!("Role {} is an ERC-1155 role without a token_id", self.id);
+        }
+        if self.min_balance.is_zero() {
+            bail!("Role {} has a min_balance of 0", self.id);
+        }
+        Ok(())
+    }
+}
+
+/// Role of the delegates in a tier of voting power. The index only holds
+/// the latest votes, so the role is left out of snapshot requests and listed
+/// in their `latest_only`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct DelegateTierConfig {
+    pub id: String,
+    /// Role number signed in the identity, distinct from the other roles.
+    pub role: u8,
+    pub label: String,
+    #[serde(flatten)]
+    pub tier: DelegateTier,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "tier", rename_all = "snake_case")]
+pub enum DelegateTier {
+    /// At least `min_votes` votes, in the token's smallest unit.
+    MinVotes { min_votes: U256 },
+    /// At least `min_bps` basis points of the votable supply, the votes of
+    /// all delegates.
+    MinShare { min_bps: u32 },
+    /// Among the `top` delegates by votes. Delegates tied with the last one
+    /// are included.
+    Top { top: usize },
+}
+
+impl DelegateTierConfig {
+    pub fn validate(&self) -> Result<()> {
+        match self.tier {
+            DelegateTier::MinVotes { min_votes } if min_votes.is_zero() => {
+                bail!("Role {} has a min_votes of 0", self.id)
+            }
+            DelegateTier::MinShare { min_bps } if min_bps == 0 || min_bps > 10_000 => {
+                bail!("Role {} has a min_bps outside of 1..=10000", self.id)
+            }
+            DelegateTier::Top { top: 0 } => bail!("Role {} has a top of 0", self.id),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Scanning of the `DelegateVotesChanged` events of the delegate index.
+#[derive(Debug, Clone, Deserialize)]
+pub struct DelegateIndexConfig {
+    /// First block scanned, the token's deployment block or any before its
+    /// first delegation.
+    pub from_block: u64,
+    /// Blocks per `eth_getLogs` request.
+    #[serde(default = "default_chunk_blocks")]
+    pub chunk_blocks: u64,
+    #[serde(default = "default_refresh_seconds")]
+    pub poll_seconds: u64,
+    /// Depth under the latest block of the last block indexed, so indexed
+    /// events are not reorganized away.
+    #[serde(default = "default_confirmations")]
+    pub confirmations: u64,
+}
+
+/// Sponsored ERC-4337 relaying. The account is owned by `USER_OP_OWNER_KEY`
+/// and the paymaster's verifying signer is `PAYMASTER_SIGNER_KEY`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct UserOpConfig {
+    pub bundler_url: String,
+    #[serde(default = "default_entry_point")]
+    pub entry_point: Address,
+    /// Deployed smart account executing the calls.
+    pub account: Address,
+    /// VerifyingPaymaster sponsoring the operations.
+    pub paymaster: Address,
+    /// Validity of a paymaster signature.
+    #[serde(default = "default_sponsor_seconds")]
+    pub sponsor_seconds: u64,
+}
+
+/// Transaction handling of the relayer wallets.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RelayerConfig {
+    /// Interval between checks of pending transactions.
+    pub poll_seconds: u64,
+    /// Time without a receipt before a transaction is replaced.
+    pub stuck_seconds: u64,
+    /// Fee increase of each replacement, at least 10 for most nodes.
+    pub fee_bump_percent: u128,
+    /// Broadcast attempts before a job fails.
+    pub max_attempts: u32,
+    /// Mixing offered to calls that opt in, disabled when unset.
+    pub mixing: Option<Mixing>,
+    /// Top up pool wallets under `low_balance` from the richest one, instead
+    /// of only warning.
+    pub rebalance: bool,
+}
+
+impl Default for RelayerConfig {
+    fn default() -> Self {
+        RelayerConfig {
+            poll_seconds: 4,
+            stuck_seconds: 60,
+            fee_bump_percent: 20,
+            max_attempts: 3,
+            mixing: None,
+            rebalance: false,
+        }
+    }
+}
+
+/// Admission of calls to the relayer, see `relayer::Gate`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct GateConfig {
+    /// Leading zero bits required of a proof of work under normal load.
+    pub base_difficulty: u32,
+    pub max_difficulty: u32,
+    /// Accepted calls per minute before the difficulty starts to rise.
+    pub target_per_minute: u64,
+    /// Allowed distance between a proof of work timestamp and now.
+    pub pow_ttl: u64,
+}
+
+impl Default for GateConfig {
+    fn default() -> Self {
+        GateConfig {
+            base_difficulty: 16,
+            max_difficulty: 24,
+            target_per_minute: 30,
+            pow_ttl: 300,
+        }
+    }
+}
+
+/// Limits on what the relayer wallets of a network may spend, amounts in wei.
+/// Unset limits are not enforced. The budget is shared by the pool, balances
+/// are checked per wallet. Sponsored user operations are held to the same
+/// limits, with a budget of their own and the paymaster's EntryPoint deposit
+/// as balance.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct SpendingLimits {
+    /// Calls estimated above this gas limit are refused.
+    pub max_gas_limit: Option<u128>,
+    /// Fee caps per gas, also capping replacements.
+    pub max_fee_per_gas: Option<u128>,
+    pub max_priority_fee_per_gas: Option<u128>,
+    /// Worst-case cost of the transactions sent per UTC day.
+    pub daily_budget: Option<u128>,
+    /// Balance under which a warning is raised.
+    pub low_balance: u128,
+    /// Balance under which relaying stops.
+    pub min_balance: u128,
+}
+
+impl SpendingLimits {
+    /// Most a single transaction may cost, zero when gas or fees are uncapped.
+    pub fn worst_case(&self) -> u128 {
+        self.max_gas_limit
+            .zip(self.max_fee_per_gas)
+            .map_or(0, |(gas, fee)| gas * fee)
+    }
+
+    /// Whether `cost` more wei fits in the budget, given what was spent today.
+    pub fn check_budget(&self, spent: u128, cost: u128) -> Result<(), RelayError> {
+        match self.daily_budget {
+            Some(budget) if spent.saturating_add(cost) > budget => Err(RelayError::BudgetExhausted),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn default_refresh_seconds() -> u64 {
+    60
+}
+
+fn default_chunk_blocks() -> u64 {
+    10_000
+}
+
+fn default_confirmations() -> u64 {
+    12
+}
+
+fn default_min_balance() -> U256 {
+    U256::from(1)
+}
+
+fn default_op_token() -> Address {
+    OPTIMISM_TOKEN_ADDRESS
+}
+
+fn default_entry_point() -> Address {
+    ENTRY_POINT_V07
+}
+
+fn default_sponsor_seconds() -> u64 {
+    600
+}
+
+fn default_epoch_seconds() -> u64 {
+    3600
+}
+
+fn default_data_dir() -> PathBuf {
+    PathBuf::from("data")
+}
+
+impl Config {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let config: Config = serde_json::from_str(
+            &fs::read_to_string(path)
+                .with_context(|| format!("Failed to read config {}", path.display()))?,
+        )
+        .with_context(|| format!("Failed to parse config {}", path.display()))?;
+
+        if !config
+            .networks
+            .iter()
+            .any(|n| n.chain_id == config.default_chain_id)
+        {
+            bail!(
+                "Default chain {} is not configured",
+                config.default_chain_id
+            );
+        }
+        for (i, network) in config.networks.iter().enumerate() {
+            if config.networks[..i]
+                .iter()
+                .any(|n| n.chain_id == network.chain_id)
+            {
+                bail!("Chain {} is configured twice", network.chain_id);
+            }
+            for role in &network.token_roles {
+                role.validate()
+                    .with_context(|| format!("Chain {}", network.chain_id))?;
+            }
+            for role in &network.delegate_tiers {
+                role.validate()
+                    .with_context(|| format!("Chain {}", network.chain_id))?;
+            }
+            match &network.delegate_index {
+                None if !network.delegate_tiers.is_empty() => bail!(
+                    "Chain {}: delegate_tiers need a delegate_index with a from_block",
+                    network.chain_id
+                ),
+                Some(index) if index.chunk_blocks == 0 || index.poll_seconds == 0 => bail!(
+                    "Chain {}: delegate_index needs a chunk_blocks and poll_seconds above 0",
+                    network.chain_id
+                ),
+                _ => {}
+            }
+            for role in &network.attestation_roles {
+                if role.refresh_seconds == 0 {
+                    bail!(
+                        "Chain {}: role {} has a refresh_seconds of 0",
+                        network.chain_id,
+                        role.id
+                    );
+                }
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::address;
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn token_roles() {
+        let role: TokenRoleConfig = serde_json::from_value(json!({
+            "id": "op-holder",
+            "role": 4,
+            "label": "OP Holder",
+            "contract": "0x4200000000000000000000000000000000000042",
+            "standard": "erc20",
+            "min_balance": "1000000000000000000",
+        }))
+        .unwrap();
+        assert_eq!(
+            role.contract,
+            address!("4200000000000000000000000000000000000042")
+        );
+        assert_eq!(role.min_balance, U256::from(10).pow(U256::from(18)));
+        assert!(role.validate().is_ok());
+
+        let role: TokenRoleConfig = serde_json::from_value(json!({
+            "id": "badge",
+            "role": 5,
+            "label": "Badge",
+            "chain_id": 1,
+            "rpc_url": "https://eth.llamarpc.com",
+            "contract": "0x0000000000000000000000000000000000000001",
+            "standard": "erc1155",
+        }))
+        .unwrap();
+        assert_eq!(role.min_balance, U256::from(1));
+        assert!(role.validate().is_err());
+        assert!(TokenRoleConfig {
+            token_id: Some(U256::from(7)),
+            ..role.clone()
+        }
+        .validate()
+        .is_ok());
+        assert!(TokenRoleConfig {
+            token_id: Some(U256::from(7)),
+            min_balance: U256::ZERO,
+            ..role
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn attestation_roles() {
+        let role: AttestationRoleConfig = serde_json::from_value(json!({
+            "id": "badgeholder",
+            "role": 1,
+            "label": "Badgeholder",
+            "schema": "0xfdcfdad2dbe7489e0ce56b260348b7f14e8365a8a325aef9834818c00d46b31b",
+            "attesters": [
+                "0x621477dBA416E12df7FF0d48E14c4D20DC85D7D9",
+                "0xE4553b743E74dA3424Ac51f8C1E586fd43aE226F"
+            ],
+            "predicate": { "field": "rpgfRound", "op": "latest" },
+        }))
+        .unwrap();
+        assert_eq!(role, AttestationRoleConfig::badgeholder());
+
+        let role: AttestationRoleConfig = serde_json::from_value(json!({
+            "id": "council",
+            "role": 6,
+            "label": "Grants Council",
+            "schema": "0x0000000000000000000000000000000000000000000000000000000000000001",
+            "predicate": { "field": "season", "op": "in", "values": ["5", "6"] },
+            "revocation": "strict",
+            "refresh_seconds": 600,
+        }))
+        .unwrap();
+        assert_eq!(
+            role.predicate.unwrap().condition,
+            Condition::In {
+                values: vec!["5".to_string(), "6".to_string()]
+            }
+        );
+        assert_eq!(role.revocation, RevocationPolicy::Strict);
+        assert!(role.attesters.is_empty());
+    }
+
+    #[test]
+    fn delegate_tiers() {
+        let tiers: Vec<DelegateTierConfig> = serde_json::from_value(json!([
+            { "id": "top-100", "role": 5, "label": "Top 100 Delegate", "tier": "top", "top": 100 },
+            { "id": "one-percent", "role": 6, "label": "1% Delegate", "tier": "min_share", "min_bps": 100 },
+            { "id": "zero", "role": 7, "label": "Zero", "tier": "min_votes", "min_votes": "0" },
+        ]))
+        .unwrap();
+        assert_eq!(tiers[0].tier, DelegateTier::Top { top: 100 });
+        assert_eq!(tiers[1].tier, DelegateTier::MinShare { min_bps: 100 });
+        assert!(tiers[0].validate().is_ok());
+        assert!(tiers[1].validate().is_ok());
+        assert!(tiers[2].validate().is_err());
+
+        let index: DelegateIndexConfig =
+            serde_json::from_value(json!({ "from_block": 6490467 })).unwrap();
+        assert_eq!((index.chunk_blocks, index.confirmations), (10_000, 12));
+        assert!(serde_json::from_value::<DelegateIndexConfig>(json!({})).is_err());
+    }
+}
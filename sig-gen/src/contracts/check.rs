@@ -0,0 +1,186 @@
+use std::fmt::{self, Display};
+
+use alloy::{
+    primitives::{Address, B256},
+    providers::Provider,
+    transports::Transport,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{AttesterClient, AttesterError};
+use crate::crypto::VERIFICATION_KEY_HASH;
+
+/// What to do when an AnonymousAttester disagrees with the local
+/// configuration at startup.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StartupCheck {
+    /// Refuse to start.
+    #[default]
+    Strict,
+    /// Start anyway and report the network as degraded on `/health`.
+    Degraded,
+}
+
+/// Mismatch between an AnonymousAttester and the server's configuration.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "issue", rename_all = "snake_case")]
+pub enum Inconsistency {
+    /// `curiaPubkey` is not the key of `PRIVATE_KEY`, so every proof built
+    /// on our signatures would fail verification.
+    PubkeyMismatch {
+        expected: [B256; 2],
+        actual: [B256; 2],
+    },
+    VerifierUnset,
+    SchemaNotAllowed {
+        schema: B256,
+    },
+    /// `value` could not be read, so it is unchecked.
+    Unreadable {
+        value: String,
+        error: String,
+    },
+}
+
+impl Display for Inconsistency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Inconsistency::PubkeyMismatch { expected, actual } => write!(
+                f,
+                "curiaPubkey is ({}, {}), expected ({}, {})",
+                actual[0], actual[1], expected[0], expected[1]
+            ),
+            Inconsistency::VerifierUnset => write!(f, "Verifier is not set"),
+            Inconsistency::SchemaNotAllowed { schema } => {
+                write!(f, "Schema {} is not allowed", schema)
+            }
+            Inconsistency::Unreadable { value, error } => {
+                write!(f, "Failed to read {}: {}", value, error)
+            }
+        }
+    }
+}
+
+/// Outcome of the startup check of an AnonymousAttester.
+#[derive(Debug, Clone, Default)]
+pub struct AttesterCheck {
+    pub issues: Vec<Inconsistency>,
+    /// Whether the attester's verifier has the verification key of
+    /// `verify_proof`, so its proofs can be checked natively.
+    pub native_proofs: bool,
+}
+
+/// Compare the on-chain configuration of `attester` with the server's public
+/// key and the network's schema, if any. Values that fail to be read are
+/// reported as `Unreadable`, a verifier without `getVerificationKeyHash`
+/// only leaves its proofs to the simulation.
+pub async fn check_attester<T: Transport + Clone, P: Provider<T>>(
+    attester: &AttesterClient<T, P>,
+    pubkey: [B256; 2],
+    schema: Option<B256>,
+) -> AttesterCheck {
+    let mut issues = vec![];
+    let curia_pubkey = read(&mut issues, "curiaPubkey", attester.curia_pubkey().await);
+    let verifier = read(&mut issues, "verifier", attester.verifier().await);
+    let schema = match schema {
+        Some(schema) => read(
+            &mut issues,
+            "schemas",
+            attester.schema_allowed(schema).await,
+        )
+        .map(|allowed| (schema, allowed)),
+        None => None,
+    };
+
+    let native_proofs = match verifier.filter(|verifier| !verifier.is_zero()) {
+        Some(verifier) => attester
+            .verification_key_hash(verifier)
+            .await
+            .is_ok_and(|hash| hash == VERIFICATION_KEY_HASH),
+        None => false,
+    };
+
+    issues.extend(inconsistencies(pubkey, curia_pubkey, verifier, schema));
+    AttesterCheck {
+        issues,
+        native_proofs,
+    }
+}
+
+fn read<V>(
+    issues: &mut Vec<Inconsistency>,
+    value: &str,
+    result: Result<V, AttesterError>,
+) -> Option<V> {
+    result
+        .map_err(|e| {
+            issues.push(Inconsistency::Unreadable {
+                value: value.to_string(),
+                error: e.to_string(),
+            })
+        })
+        .ok()
+}
+
+/// Mismatches among the values that could be read.
+pub fn inconsistencies(
+    pubkey: [B256; 2],
+    curia_pubkey: Option<[B256; 2]>,
+    verifier: Option<Address>,
+    schema: Option<(B256, bool)>,
+) -> Vec<Inconsistency> {
+    let mut issues = vec![];
+    if let Some(curia_pubkey) = curia_pubkey.filter(|curia_pubkey| *curia_pubkey != pubkey) {
+        issues.push(Inconsistency::PubkeyMismatch {
+            expected: pubkey,
+            actual: curia_pubkey,
+        });
+    }
+    if verifier.is_some_and(|verifier| verifier.is_zero()) {
+        issues.push(Inconsistency::VerifierUnset);
+    }
+    if let Some((schema, false)) = schema {
+        issues.push(Inconsistency::SchemaNotAllowed { schema });
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::{address, b256};
+
+    use super::*;
+
+    #[test]
+    fn attester_inconsistencies() {
+        let pubkey = [
+            b256!("1fc12a4421a52b9485cd696b8cfe29c1c3fcaf77f9fadccce0d47593301ad232"),
+            b256!("0dd32faaef5273e1fe22189585be98493fd3d2c2c6e61728dda32ec5335f3ce3"),
+        ];
+        let verifier = address!("000000000000000000000000000000000000dEaD");
+        let schema = b256!("298883c389b260cf2226b0511a357966e584852a580d58adc8f529bc84d5050a");
+
+        assert!(
+            inconsistencies(pubkey, Some(pubkey), Some(verifier), Some((schema, true))).is_empty()
+        );
+        assert!(inconsistencies(pubkey, Some(pubkey), Some(verifier), None).is_empty());
+        assert!(inconsistencies(pubkey, None, None, None).is_empty());
+        assert_eq!(
+            inconsistencies(
+                pubkey,
+                Some([pubkey[1], pubkey[0]]),
+                Some(Address::ZERO),
+                Some((schema, false))
+            ),
+            vec![
+                Inconsistency::PubkeyMismatch {
+                    expected: pubkey,
+                    actual: [pubkey[1], pubkey[0]],
+                },
+                Inconsistency::VerifierUnset,
+                Inconsistency::SchemaNotAllowed { schema },
+            ]
+        );
+    }
+}
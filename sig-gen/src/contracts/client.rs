@@ -0,0 +1,218 @@
+use std::fmt::{self, Debug, Display};
+
+use alloy::{
+    primitives::{Address, Bytes, TxHash, B256, U256},
+    providers::Provider,
+    sol_types::{SolCall, SolInterface},
+    transports::{Transport, TransportError},
+};
+
+use super::{
+    mapping_slot,
+    AnonymousAttester::{self, AnonymousAttesterErrors, AnonymousAttesterInstance},
+    AttestationResolver::{self, AttestationResolverErrors, AttestationResolverInstance},
+    BaseUltraVerifier,
+    KeyRegistry::{self, Key, KeyRegistryInstance},
+    NONCE_SLOT, SCHEMAS_SLOT,
+};
+
+/// Failed contract call, the revert decoded into the contract's named errors
+/// when the node returns its data.
+#[derive(Debug)]
+pub enum ContractError<E> {
+    Revert(E),
+    Call(alloy::contract::Error),
+}
+
+impl<E: SolInterface> From<alloy::contract::Error> for ContractError<E> {
+    fn from(e: alloy::contract::Error) -> Self {
+        match &e {
+            alloy::contract::Error::TransportError(transport) => match decode_revert(transport) {
+                Some(revert) => ContractError::Revert(revert),
+                None => ContractError::Call(e),
+            },
+            _ => ContractError::Call(e),
+        }
+    }
+}
+
+impl<E: Debug> Display for ContractError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContractError::Revert(e) => write!(f, "Reverted with {:?}", e),
+            ContractError::Call(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl<E: Debug> std::error::Error for ContractError<E> {}
+
+pub type AttesterError = ContractError<AnonymousAttesterErrors>;
+pub type ResolverError = ContractError<AttestationResolverErrors>;
+
+/// Revert data carried by an `eth_call` or `eth_estimateGas` error.
+pub fn revert_data(e: &TransportError) -> Option<Bytes> {
+    e.as_error_resp()
+        .and_then(|payload| payload.data.as_ref())
+        .and_then(|data| serde_json::from_str::<Bytes>(data.get()).ok())
+}
+
+/// Named error of a contract a revert decodes into.
+pub fn decode_revert<E: SolInterface>(e: &TransportError) -> Option<E> {
+    E::abi_decode(&revert_data(e)?, true).ok()
+}
+
+/// Calls of an AnonymousAttester deployment.
+#[derive(Debug, Clone)]
+pub struct AttesterClient<T, P>(AnonymousAttesterInstance<T, P>);
+
+impl<T: Transport + Clone, P: Provider<T>> AttesterClient<T, P> {
+    pub fn new(address: Address, provider: P) -> Self {
+        AttesterClient(AnonymousAttester::new(address, provider))
+    }
+
+    pub fn address(&self) -> Address {
+        *self.0.address()
+    }
+
+    /// Public key of the Curia signer, as `[x, y]` words.
+    pub async fn curia_pubkey(&self) -> Result<[B256; 2], AttesterError> {
+        Ok([
+            self.0.curiaPubkey(U256::ZERO).call().await?._0,
+            self.0.curiaPubkey(U256::from(1)).call().await?._0,
+        ])
+    }
+
+    pub async fn verifier(&self) -> Result<Address, AttesterError> {
+        Ok(self.0.verifier().call().await?._0)
+    }
+
+    /// Hash of the verification key of `verifier`, the attester's verifier.
+    pub async fn verification_key_hash(&self, verifier: Address) -> Result<B256, AttesterError> {
+        let verifier = BaseUltraVerifier::new(verifier, self.0.provider());
+        Ok(verifier.getVerificationKeyHash().call().await?._0)
+    }
+
+    pub async fn eas(&self) -> Result<Address, AttesterError> {
+        Ok(self.0.eas().call().await?._0)
+    }
+
+    pub async fn owner(&self) -> Result<Address, AttesterError> {
+        Ok(self.0.owner().call().await?._0)
+    }
+
+    /// Whether `schema` is allowed, read from the storage of the contract as
+    /// the mapping has no getter.
+    pub async fn schema_allowed(&self, schema: B256) -> Result<bool, AttesterError> {
+        self.flag(mapping_slot(schema, SCHEMAS_SLOT)).await
+    }
+
+    /// Whether `nonce` was used, read from storage like `schema_allowed`.
+    pub async fn nonce_used(&self, nonce: B256) -> Result<bool, AttesterError> {
+        self.flag(mapping_slot(nonce, NONCE_SLOT)).await
+    }
+
+    async fn flag(&self, slot: U256) -> Result<bool, AttesterError> {
+        let word = self
+            .0
+            .provider()
+            .get_storage_at(self.address(), slot)
+            .await
+            .map_err(alloy::contract::Error::TransportError)?;
+        Ok(!word.is_zero())
+    }
+
+    /// UID the attestation would get if sent from `from` now.
+    pub async fn simulate_attest(
+        &self,
+        call: &AnonymousAttester::attestCall,
+        from: Address,
+    ) -> Result<B256, AttesterError> {
+        Ok(self.simulate(call, from).await?._0)
+    }
+
+    /// Result of `call` if sent from `from` now.
+    pub async fn simulate<C: SolCall>(
+        &self,
+        call: &C,
+        from: Address,
+    ) -> Result<C::Return, AttesterError> {
+        Ok(self.0.call_builder(call).from(from).call().await?)
+    }
+
+    pub async fn attest(
+        &self,
+        call: &AnonymousAttester::attestCall,
+    ) -> Result<TxHash, AttesterError> {
+        self.send(call).await
+    }
+
+    pub async fn revoke(
+        &self,
+        call: &AnonymousAttester::revokeCall,
+    ) -> Result<TxHash, AttesterError> {
+        self.send(call).await
+    }
+
+    /// Send `call` from the provider's wallet.
+    pub async fn send<C: SolCall>(&self, call: &C) -> Result<TxHash, AttesterError> {
+        Ok(*self.0.call_builder(call).send().await?.tx_hash())
+    }
+}
+
+/// Calls of an AttestationResolver deployment.
+#[derive(Debug, Clone)]
+pub struct ResolverClient<T, P>(AttestationResolverInstance<T, P>);
+
+impl<T: Transport + Clone, P: Provider<T>> ResolverClient<T, P> {
+    pub fn new(address: Address, provider: P) -> Self {
+        ResolverClient(AttestationResolver::new(address, provider))
+    }
+
+    pub fn address(&self) -> Address {
+        *self.0.address()
+    }
+
+    pub async fn is_authorized(&self, attester: Address) -> Result<bool, ResolverError> {
+        Ok(self.0.authorizedAttesters(attester).call().await?._0)
+    }
+
+    /// Resolver checking attesters of `role`, zero when the role is unknown.
+    pub async fn custom_resolver(&self, role: U256) -> Result<Address, ResolverError> {
+        Ok(self.0.customResolvers(role).call().await?._0)
+    }
+
+    pub async fn owner(&self) -> Result<Address, ResolverError> {
+        Ok(self.0.owner().call().await?._0)
+    }
+
+    /// Send `call` from the provider's wallet.
+    pub async fn send<C: SolCall>(&self, call: &C) -> Result<TxHash, ResolverError> {
+        Ok(*self.0.call_builder(call).send().await?.tx_hash())
+    }
+}
+
+/// Calls of a KeyRegistry deployment, which has no named errors.
+#[derive(Debug, Clone)]
+pub struct RegistryClient<T, P>(KeyRegistryInstance<T, P>);
+
+impl<T: Transport + Clone, P: Provider<T>> RegistryClient<T, P> {
+    pub fn new(address: Address, provider: P) -> Self {
+        RegistryClient(KeyRegistry::new(address, provider))
+    }
+
+    pub fn address(&self) -> Address {
+        *self.0.address()
+    }
+
+    /// Key registered by `address`, `None` if it has none.
+    pub async fn key(&self, address: Address) -> Result<Option<Key>, alloy::contract::Error> {
+        let key = self.0.key(address).call().await?._0;
+        Ok((key.x != B256::ZERO || key.y != B256::ZERO).then_some(key))
+    }
+
+    /// Register `key` for the provider's wallet.
+    pub async fn register_key(&self, key: Key) -> Result<TxHash, alloy::contract::Error> {
+        Ok(*self.0.registerKey(key).send().await?.tx_hash())
+    }
+}
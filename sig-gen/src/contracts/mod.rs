@@ -0,0 +1,6 @@
+pub mod check;
+pub use check::*;
+pub mod client;
+pub use client::*;
+pub mod types;
+pub use types::*;
@@ -0,0 +1,246 @@
+use alloy::{
+    primitives::{b256, keccak256, B256, U256},
+    sol,
+};
+
+sol! {
+    #![sol(all_derives)]
+
+    struct Schema {
+        uint256 role;
+        string title;
+        string message;
+        bytes ref;
+    }
+
+    struct AttestationProof {
+        bytes proof;
+        bytes32 revokerHash;
+        bytes32 nonce;
+        bytes32 timestamp;
+    }
+
+    #[sol(rpc)]
+    contract AnonymousAttester {
+        error RevokerNotFound();
+        error InvalidRevoker();
+        error InvalidSchema();
+        error NonceUsed();
+        error InvalidProof();
+        error InvalidVerifier();
+        error OwnableUnauthorizedAccount(address account);
+        error OwnableInvalidOwner(address owner);
+
+        event SchemaAdded(bytes32 indexed schema);
+        event SchemaRemoved(bytes32 indexed schema);
+        event VerifierChanged(address indexed verifier);
+        event OwnershipTransferred(address indexed previousOwner, address indexed newOwner);
+
+        function eas() external view returns (address);
+        function verifier() external view returns (address);
+        function curiaPubkey(uint256 index) external view returns (bytes32);
+        function owner() external view returns (address);
+
+        function changePubkey(bytes32[2] calldata pubkey) external;
+        function changeVerifier(address verifier) external;
+        function addSchema(bytes32 schema) external;
+        function removeSchema(bytes32 schema) external;
+        function transferOwnership(address newOwner) external;
+        function renounceOwnership() external;
+
+        function attest(bytes32 schema, address recipient, Schema calldata data, AttestationProof calldata proof) external returns (bytes32);
+        function revoke(bytes32 schema, bytes32 uid, bytes32 revoker) external;
+    }
+
+    contract SimpleAccount {
+        function execute(address dest, uint256 value, bytes calldata func) external;
+    }
+
+    #[sol(rpc)]
+    contract BaseUltraVerifier {
+        function getVerificationKeyHash() external view returns (bytes32);
+    }
+
+    #[sol(rpc)]
+    contract EntryPoint {
+        function balanceOf(address account) external view returns (uint256);
+    }
+
+    contract EAS {
+        event Attested(address indexed recipient, address indexed attester, bytes32 uid, bytes32 indexed schemaUID);
+    }
+}
+
+/// Topic of `AnonymousAttester.PubkeyChanged(bytes32[2] indexed pubkey)`,
+/// which `sol!` cannot bind for its indexed array.
+pub const PUBKEY_CHANGED: B256 =
+    b256!("0a10610fe8631a690ea659383bbacb47dccdc72e11e19e7f8307316802d9f8e5");
+
+/// Indexed `pubkey` of a `PubkeyChanged` log, the hash of both words.
+pub fn pubkey_topic(pubkey: [B256; 2]) -> B256 {
+    keccak256(pubkey.concat())
+}
+
+/// Storage slots of the private `schemas` and `nonce` mappings of
+/// AnonymousAttester, after `Ownable._owner`, `verifier`, the two words of
+/// `curiaPubkey` and the `revokers` mapping at slot 4.
+pub const SCHEMAS_SLOT: u64 = 5;
+pub const NONCE_SLOT: u64 = 6;
+
+/// Storage slot of `key` in a `bytes32` keyed mapping declared at `slot`.
+pub fn mapping_slot(key: B256, slot: u64) -> U256 {
+    let slot = U256::from(slot).to_be_bytes::<32>();
+    U256::from_be_bytes(keccak256([key.0, slot].concat()).0)
+}
+
+// Separate blocks, as `sol!` refuses the Ownable items repeated in one.
+sol! {
+    #![sol(all_derives)]
+
+    #[sol(rpc)]
+    contract AttestationResolver {
+        struct Attestation {
+            bytes32 uid;
+            bytes32 schema;
+            uint64 time;
+            uint64 expirationTime;
+            uint64 revocationTime;
+            bytes32 refUID;
+            address recipient;
+            address attester;
+            bool revocable;
+            bytes data;
+        }
+
+        error InvalidRole();
+        error AccessDenied();
+        error InvalidEAS();
+        error InvalidLength();
+        error InsufficientValue();
+        error NotPayable();
+        error OwnableUnauthorizedAccount(address account);
+        error OwnableInvalidOwner(address owner);
+
+        // sic, as emitted by the contract
+        event AuthoriedAttesterAdded(address indexed attester);
+        event AuthoriedAttesterRemoved(address indexed attester);
+        event CustomResolverSet(uint256 indexed role, address resolver);
+        event OwnershipTransferred(address indexed previousOwner, address indexed newOwner);
+
+        function authorizedAttesters(address attester) external view returns (bool);
+        function customResolvers(uint256 role) external view returns (address);
+        function isPayable() external pure returns (bool);
+        function version() external view returns (string memory);
+        function owner() external view returns (address);
+
+        function addAuthorizedAttester(address attester) external;
+        function removeAuthorizedAttester(address attester) external;
+        function setCustomResolver(uint256 role, address resolver) external;
+        function transferOwnership(address newOwner) external;
+        function renounceOwnership() external;
+
+        // only callable by EAS
+        function attest(Attestation calldata attestation) external payable returns (bool);
+        function multiAttest(Attestation[] calldata attestations, uint256[] calldata values) external payable returns (bool);
+        function revoke(Attestation calldata attestation) external payable returns (bool);
+        function multiRevoke(Attestation[] calldata attestations, uint256[] calldata values) external payable returns (bool);
+    }
+}
+
+sol! {
+    #![sol(all_derives)]
+
+    #[sol(rpc)]
+    contract KeyRegistry {
+        struct Key {
+            bytes32 x;
+            bytes32 y;
+        }
+
+        event KeyRegistered(address indexed addr, bytes32 x, bytes32 y);
+
+        function key(address addr) external view returns (Key memory);
+        function registerKey(Key memory k) external;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::{
+        primitives::{address, hex},
+        sol_types::{SolEvent, SolInterface},
+    };
+
+    use super::*;
+
+    #[test]
+    fn revert_data_and_topics() {
+        assert_eq!(
+            AnonymousAttester::AnonymousAttesterErrors::abi_decode(&hex!("1f6d5aef"), true)
+                .unwrap(),
+            AnonymousAttester::AnonymousAttesterErrors::NonceUsed(AnonymousAttester::NonceUsed {})
+        );
+        assert_eq!(
+            AnonymousAttester::AnonymousAttesterErrors::abi_decode(
+                &hex!("118cdaa7000000000000000000000000000000000000000000000000000000000000dead"),
+                true
+            )
+            .unwrap(),
+            AnonymousAttester::AnonymousAttesterErrors::OwnableUnauthorizedAccount(
+                AnonymousAttester::OwnableUnauthorizedAccount {
+                    account: address!("000000000000000000000000000000000000dEaD")
+                }
+            )
+        );
+        assert_eq!(
+            AttestationResolver::AttestationResolverErrors::abi_decode(&hex!("d954416a"), true)
+                .unwrap(),
+            AttestationResolver::AttestationResolverErrors::InvalidRole(
+                AttestationResolver::InvalidRole {}
+            )
+        );
+
+        assert_eq!(PUBKEY_CHANGED, keccak256("PubkeyChanged(bytes32[2])"));
+        assert_eq!(
+            KeyRegistry::KeyRegistered::SIGNATURE_HASH,
+            keccak256("KeyRegistered(address,bytes32,bytes32)")
+        );
+    }
+
+    #[test]
+    fn attester_bindings_exist_in_the_contract() {
+        assert_eq!(
+            mapping_slot(B256::ZERO, 0),
+            U256::from_be_bytes(
+                b256!("ad3228b676f7d3cd4284a5443f17f1962b36e491b30a40b2405849e597ba5fb5").0
+            )
+        );
+
+        let bindings = include_str!("types.rs");
+        let contract = include_str!("../../../contracts/src/AnonymousAttester.sol");
+        let ownable = ["owner", "transferOwnership", "renounceOwnership"];
+
+        let start = bindings.find("contract AnonymousAttester {").unwrap();
+        let end = start + bindings[start..].find("\n    }").unwrap();
+        for line in bindings[start..end].lines().map(str::trim) {
+            let Some(name) = line
+                .strip_prefix("function ")
+                .and_then(|f| f.split('(').next())
+            else {
+                continue;
+            };
+            let declared = [
+                format!("function {}(", name),
+                format!("public {};", name),
+                format!("public immutable {};", name),
+            ]
+            .iter()
+            .any(|declaration| contract.contains(declaration.as_str()));
+            assert!(
+                declared || ownable.contains(&name),
+                "{} is not a function of AnonymousAttester",
+                name
+            );
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use alloy::primitives::Address;
+use anyhow::Result;
+use ark_bn254::Fr;
+use ark_ff::PrimeField;
+
+use super::hash;
+
+/// Identity signed by Curia and checked by the circuit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Identity {
+    pub address: Address,
+    pub role: u8,
+    pub timestamp: u64,
+    pub random_nonce: Fr,
+}
+
+impl Identity {
+    /// `H(address, role, timestamp, random_nonce)`, the message of `circuits`.
+    pub fn hash(&self) -> Result<Fr> {
+        hash(&[
+            Fr::from_be_bytes_mod_order(self.address.as_ref()),
+            Fr::from(self.role),
+            Fr::from(self.timestamp),
+            self.random_nonce,
+        ])
+    }
+}
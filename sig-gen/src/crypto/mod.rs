@@ -8,6 +8,14 @@ use light_poseidon::{Poseidon, PoseidonHasher};
 
 pub mod affine;
 pub use affine::*;
+pub mod identity;
+pub use identity::*;
+pub mod public_inputs;
+pub use public_inputs::*;
+pub mod ultraplonk;
+pub use ultraplonk::*;
+pub mod witness;
+pub use witness::*;
 
 pub fn pk8(sk: EdFr) -> EdAffine {
     let base8 = EdAffine::generator() * EdFr::from(8);
@@ -126,6 +134,7 @@ pub fn pubkey_to_address(q: ark_secp256k1::Affine) -> Address {
 
 #[cfg(test)]
 mod tests {
+    use alloy::primitives::address;
     use anyhow::Result;
     use ark_bn254::Fr;
     use ark_ec::{AffineRepr, CurveGroup};
@@ -172,6 +181,26 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn identity_hash() -> Result<()> {
+        let identity = Identity {
+            address: address!("000000000000000000000000000000000000dEaD"),
+            role: 1,
+            timestamp: 1718875852,
+            random_nonce: Fr::from(123456789000u64),
+        };
+        assert_eq!(
+            identity.hash()?,
+            hash(&[
+                Fr::from(57005u64),
+                Fr::from(1u64),
+                Fr::from(1718875852u64),
+                Fr::from(123456789000u64)
+            ])?
+        );
+        Ok(())
+    }
+
     #[test]
     fn print_noir_verify() -> Result<()> {
         let mock_rng = &mut test_rng();
@@ -0,0 +1,165 @@
+use alloy::{
+    primitives::{keccak256, Address, Bytes, B256, U256},
+    sol_types::SolCall,
+};
+use ark_bn254::Fr;
+use ark_ff::{BigInteger, PrimeField};
+
+use super::{convert, EdAffine};
+use crate::contracts::{AnonymousAttester, AttestationProof, Schema};
+
+/// Public inputs of an attestation proof, as `AnonymousAttester.attest` hands
+/// them to the verifier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PublicInputs {
+    /// `curiaPubkey`.
+    pub pubkey: [B256; 2],
+    pub role: B256,
+    /// `keccak256(title ‖ message) mod p`.
+    pub msg: B256,
+    pub nonce: B256,
+    pub timestamp: B256,
+    pub revoker_hash: B256,
+}
+
+impl PublicInputs {
+    /// Inputs the contract builds for `call` under `pubkey`.
+    pub fn from_call(pubkey: [B256; 2], call: &AnonymousAttester::attestCall) -> Self {
+        PublicInputs {
+            pubkey,
+            role: call.data.role.into(),
+            msg: message_hash(&call.data.title, &call.data.message),
+            nonce: call.proof.nonce,
+            timestamp: call.proof.timestamp,
+            revoker_hash: call.proof.revokerHash,
+        }
+    }
+
+    /// In the order of the circuit's public inputs, the revoker hash last.
+    pub fn to_array(&self) -> [B256; 7] {
+        [
+            self.pubkey[0],
+            self.pubkey[1],
+            self.role,
+            self.msg,
+            self.nonce,
+            self.timestamp,
+            self.revoker_hash,
+        ]
+    }
+}
+
+/// An anonymous attestation to submit, its proof aside.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attestation {
+    pub schema: B256,
+    pub recipient: Address,
+    pub role: u8,
+    pub title: String,
+    pub message: String,
+    pub nonce: B256,
+    pub timestamp: u64,
+    pub revoker_hash: B256,
+}
+
+impl Attestation {
+    pub fn call(&self, proof: Bytes) -> AnonymousAttester::attestCall {
+        AnonymousAttester::attestCall {
+            schema: self.schema,
+            recipient: self.recipient,
+            data: Schema {
+                role: U256::from(self.role),
+                title: self.title.clone(),
+                message: self.message.clone(),
+                r#ref: Bytes::new(),
+            },
+            proof: AttestationProof {
+                proof,
+                revokerHash: self.revoker_hash,
+                nonce: self.nonce,
+                timestamp: B256::from(U256::from(self.timestamp)),
+            },
+        }
+    }
+
+    /// `attest` calldata carrying `proof`.
+    pub fn calldata(&self, proof: Bytes) -> Bytes {
+        self.call(proof).abi_encode().into()
+    }
+
+    pub fn public_inputs(&self, pubkey: [B256; 2]) -> PublicInputs {
+        PublicInputs::from_call(pubkey, &self.call(Bytes::new()))
+    }
+}
+
+/// `uint256(keccak256(bytes(string.concat(title, message)))) % p`.
+pub fn message_hash(title: &str, message: &str) -> B256 {
+    let modulus = U256::from_limbs(Fr::MODULUS.0);
+    let hash = U256::from_be_bytes(keccak256([title, message].concat()).0);
+    (hash % modulus).into()
+}
+
+/// `curiaPubkey` words of a public key.
+pub fn pubkey_words(pubkey: &EdAffine) -> [B256; 2] {
+    [
+        word(&convert::<Fr>(&pubkey.x)),
+        word(&convert::<Fr>(&pubkey.y)),
+    ]
+}
+
+fn word(value: &Fr) -> B256 {
+    B256::from_slice(&value.into_bigint().to_bytes_be())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::b256;
+
+    use super::*;
+
+    #[test]
+    fn attest_inputs_and_calldata() {
+        let attestation = Attestation {
+            schema: b256!("298883c389b260cf2226b0511a357966e584852a580d58adc8f529bc84d5050a"),
+            recipient: Address::ZERO,
+            role: 1,
+            title: "Hello, ".to_string(),
+            message: "world!".to_string(),
+            nonce: B256::from(U256::from(123456789)),
+            timestamp: 1718875852,
+            revoker_hash: b256!("156244c131d6f68246496786659cf2e7a1119e83b3e3d88173f1eb5ce3b110cf"),
+        };
+        let pubkey = [
+            b256!("1fc12a4421a52b9485cd696b8cfe29c1c3fcaf77f9fadccce0d47593301ad232"),
+            b256!("0dd32faaef5273e1fe22189585be98493fd3d2c2c6e61728dda32ec5335f3ce3"),
+        ];
+
+        // circuits/Verifier.toml
+        assert_eq!(
+            attestation.public_inputs(pubkey).to_array(),
+            [
+                pubkey[0],
+                pubkey[1],
+                b256!("0000000000000000000000000000000000000000000000000000000000000001"),
+                b256!("25b481cf08c5d3aa7f05b7dd27d150b6ae920dea0f56622c867e8bc6544c50e1"),
+                b256!("00000000000000000000000000000000000000000000000000000000075bcd15"),
+                b256!("000000000000000000000000000000000000000000000000000000006673f6cc"),
+                attestation.revoker_hash,
+            ]
+        );
+        assert_eq!(
+            message_hash("", "Hello, world!"),
+            message_hash("Hello, ", "world!")
+        );
+
+        let proof = Bytes::from_static(&[0xde, 0xad, 0xbe, 0xef]);
+        let call =
+            AnonymousAttester::attestCall::abi_decode(&attestation.calldata(proof.clone()), true)
+                .unwrap();
+        assert_eq!(call.proof.proof, proof);
+        assert_eq!(
+            PublicInputs::from_call(pubkey, &call),
+            attestation.public_inputs(pubkey)
+        );
+    }
+}
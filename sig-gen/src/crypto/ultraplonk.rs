@@ -0,0 +1,569 @@
+use alloy::primitives::{b256, keccak256, B256, U256};
+use anyhow::{anyhow, bail, ensure, Result};
+use ark_bn254::{Bn254, Fq, Fq2, Fr, G1Affine, G1Projective, G2Affine};
+use ark_ec::{pairing::Pairing, AffineRepr, CurveGroup};
+use ark_ff::{BigInteger, Field, One, PrimeField, Zero};
+
+/// Bytes of a proof of `circuits`: commitments to the wires, sorted list,
+/// grand products and quotient, the 41 evaluations at ζ and ζω, then the two
+/// opening commitments.
+pub const PROOF_SIZE: usize = 0x860;
+
+/// Public inputs of `circuits`, see `PublicInputs::to_array`.
+pub const NUM_INPUTS: usize = 7;
+
+/// `getVerificationKeyHash()` of the verifiers of contracts/src/Verifier.sol,
+/// whose verification key follows.
+pub const VERIFICATION_KEY_HASH: B256 =
+    b256!("79981a64dbe5899aa37fd33b09e8dd58667332a50e4903c61b9ca54bf755fc35");
+const CIRCUIT_SIZE: u32 = 0x8000;
+const WORK_ROOT: &str = "2d1ba66f5941dc91017171fa69ec2bd0022a2a2d4115a009a93458fd4e26ecfb";
+const WORK_ROOT_INVERSE: &str = "05d33766e4590b3722701b6f2fa43d0dc3f028424d384e68c92a742fb2dbc0b4";
+const DOMAIN_INVERSE: &str = "3063edaa444bddc677fcd515f614555a777997e0a9287d1e62bf6dd004d82001";
+/// `[x]₂` of the SRS, as `x.c1, x.c0, y.c1, y.c0`.
+const G2_X: [&str; 4] = [
+    "260e01b251f6f1c7e7ff4e580791dee8ea51d87a358e038b4efe30fac09383c1",
+    "0118c4d5b837bcc2bc89b5b398b5974e9f5944073b32078b7e231fec938883b0",
+    "04fc6369f7110fe3d25156c1bb9a72859cf2a04641f99ba4ee413c80da6a5fe4",
+    "22febda3c0c0632a56475b4214e5615e11e6dd3f96e6cea2854a87d4dacc5e55",
+];
+/// Selector, permutation, table and identity commitments, in the order they
+/// are opened, after the seven of the proof.
+const VK_COMMITMENTS: [[&str; 2]; 23] = [
+    // Q1
+    [
+        "0c8c9fb076c8dbc15841296a6ca227514ed91e8383d5078f294f1e6a8efd255c",
+        "234eacf20adf546a1e6d60dd22a34f1214a4d2a0da0f6f8aab550e2e015612fb",
+    ],
+    // Q2
+    [
+        "125ce95b9e1b497303a25d3729477922a90a4949290ef894d5930989f965e06c",
+        "1752195a3f103463065c43819a62399eb8a56b8c1311f2c9782d513e39c2dc4c",
+    ],
+    // Q3
+    [
+        "162b0e14612450959754a66acd89448e6af76958330b756d5dc5b310a17b0839",
+        "0993b591d373e5666b5cbdd9cfd775be242ef2b7abd03948ddb5de624d5d295d",
+    ],
+    // Q4
+    [
+        "22b8dbe4994c16a635956f8e1eabb05566552154764f17e52b965c9e174e2143",
+        "094cf834592365568fa48db329b4298bd60733fcbdaf45ded4162c71a6e1431c",
+    ],
+    // Q_M
+    [
+        "2ca8fdd67249b3a65e795b5eb27bba93c2937833b112d0093bae53371fdabf22",
+        "13c52c9d83e0cdcecdab09aa97d668c47c40baa5743e3e0ef8fcac4d2ae9c87f",
+    ],
+    // Q_C
+    [
+        "0cd98b824390b79730aa8a084f84e9f49d28175b31fd57b9bdb7b94c4251e618",
+        "09c1b5568bce3b1aed124dc7ccea0a100d2e7b88b6040ada1f3a7268278f8bc8",
+    ],
+    // Q_ARITHMETIC
+    [
+        "290abdff3066a57973ed8c64f98a3ffd8cb8793ce108698f95e33db4fdcee125",
+        "0cd5332061ea66f2d163afcef2ee1ddf2da14b862861eef01af2d1d7c5ab57c2",
+    ],
+    // QSORT
+    [
+        "24900c21916471664f11c7ecc2046ce1d3bc6e81e0d5ce3ef503ace2e1f1f1e4",
+        "0e88ca7f68edb0227c78bab9bf62659b9243ed549ce3594a2cb5082403ef41dd",
+    ],
+    // Q_ELLIPTIC
+    [
+        "1553a322a8f1d271a137c0979a3495bf5cb0bf73ec0788eac89feecb33ee1d36",
+        "2c5020335ed81aeb4d4c36c7fda104dfa70bf6c878645572d81e3fecdd44226b",
+    ],
+    // Q_AUX
+    [
+        "2702bb3039ea96a2fcb819d645c9ec9a505ff837a86b8b5f0b022d383783ce81",
+        "0a1f7547b38ddcd5a6171944e982736312001267700817fba763069e38d60781",
+    ],
+    // SIGMA1
+    [
+        "1093c29a07e175d2a602dde3c46468447a51e54575e2d02caf3976da59981ceb",
+        "2b8b830fe13d8cd974f322b22693432fc3b27fbb6a60aab0b4cdc83373ba1c30",
+    ],
+    // SIGMA2
+    [
+        "21f98b685c416a632199893778d1ed8a209585605dda353de050ea83b8ef47d1",
+        "20d4f32e3ed7af3b1cb34d3cfe184e4f043e40b661c80d56e454c344053a222a",
+    ],
+    // SIGMA3
+    [
+        "25c5adea29669d1b871c6e250b992d604b6ffd53b73a6bdeee720d3600733301",
+        "1f67fef782ec75714fe45b117ad3ca14c7b88ce7725b2bdc50f728d2bbe49063",
+    ],
+    // SIGMA4
+    [
+        "07bc0a04fb7b8fe981b20b5bae5b8d4dca941a5dc005c2511604c112261f7bc0",
+        "1480b93bef734754bf46d01f84ff2110e5365fe1f99aae2e651dccd6e974a34a",
+    ],
+    // TABLE1
+    [
+        "2a4b5f15f0c5e83211d2207052885029faa830935de4ee824793f6a0c84d79b6",
+        "1a10860182e61b869402afdef5d45e31a5974e2af7f622d36a129cb70c7b68d4",
+    ],
+    // TABLE2
+    [
+        "1a5981c9a4b4aa3193ad332189d54d560212dee6a6d22c5ee921a6940bd58485",
+        "18d80af0cf3cb4291ff458d0f4880c18a2b8e6e2b9007e7369e036ab62bef00b",
+    ],
+    // TABLE3
+    [
+        "04471ce1d5d62feb4df8dd707a6e8880018a958516e51ccca38e49b053ead14d",
+        "2427fc36ab79bb7e48021c5ef1ee9a8047f180d75752b61b11021f50185f2506",
+    ],
+    // TABLE4
+    [
+        "251d0887be81ffdae4d66905d465855513c2c5fb9f49de6ca930c468af3a956f",
+        "0a9a9f26bb550a8632609ee66f4c60fd8f2f684af2f4f69ec7bb03471957791d",
+    ],
+    // TABLE_TYPE
+    [
+        "2eba790a626511360fce04085c79bc207f09814037901c5e48d127f500fea9d4",
+        "0cab4032623220784e877e2ae68f38f0bc8dbc71c7a4b59631cf06997a768202",
+    ],
+    // ID1
+    [
+        "14957308cae22e40244176a94966ac91b43097cec733f671431793deae24455a",
+        "17196aee666e5e635a9d5f22949af197f2dedea67455076679b058124ad3941b",
+    ],
+    // ID2
+    [
+        "209229ba87c11e0b9af1dc17cf814d7ddd8b90c88084520f2e2e87a1cdedb519",
+        "17a4cb5f6ca760e1090cb818b02b882d17b6824af824cde50408554d90860374",
+    ],
+    // ID3
+    [
+        "0847169728e686aa818be05628412ad13562d333ca86df1f3100a88f1cf740b9",
+        "2c5f80dcbb4f41876ba5558245563f27fda14394be225673cb13ea5b28a9e4fd",
+    ],
+    // ID4
+    [
+        "0293a587330851f33b9b591b85af455c4cb5e6ebe6bfedf8d255163e107799d0",
+        "29d36470e72d559bf8295b9eaa3caff9e5ce0e8c67969d63d248a23625b5cfc2",
+    ],
+];
+
+// Evaluations of the proof, at ζ then at ζω.
+const W1: usize = 0;
+const W2: usize = 1;
+const W3: usize = 2;
+const W4: usize = 3;
+const S: usize = 4;
+const Z: usize = 5;
+const Z_LOOKUP: usize = 6;
+const Q1: usize = 7;
+const Q2: usize = 8;
+const Q3: usize = 9;
+const Q4: usize = 10;
+const QM: usize = 11;
+const QC: usize = 12;
+const QARITH: usize = 13;
+const QSORT: usize = 14;
+const QELLIPTIC: usize = 15;
+const QAUX: usize = 16;
+const SIGMA1: usize = 17;
+const TABLE1: usize = 21;
+const TABLE_TYPE: usize = 25;
+const ID1: usize = 26;
+const W1_OMEGA: usize = 30;
+const W2_OMEGA: usize = 31;
+const W3_OMEGA: usize = 32;
+const W4_OMEGA: usize = 33;
+const S_OMEGA: usize = 34;
+const Z_OMEGA: usize = 35;
+const Z_LOOKUP_OMEGA: usize = 36;
+const TABLE1_OMEGA: usize = 37;
+const NUM_EVALUATIONS: usize = 41;
+
+// Commitments of the proof.
+const T1: usize = 7;
+const PI_Z: usize = 11;
+const PI_Z_OMEGA: usize = 12;
+
+/// Verify an UltraPlonk proof of `circuits` as `UltraVerifier.verify` does,
+/// without a chain. Fails with the name of the contract's revert reason.
+pub fn verify_proof(proof: &[u8], public_inputs: &[B256]) -> Result<()> {
+    ensure!(
+        proof.len() == PROOF_SIZE,
+        "Proof of {} bytes, expected {}",
+        proof.len(),
+        PROOF_SIZE
+    );
+    ensure!(
+        public_inputs.len() == NUM_INPUTS,
+        "PUBLIC_INPUT_COUNT_INVALID({}, {})",
+        NUM_INPUTS,
+        public_inputs.len()
+    );
+    let modulus = U256::from_limbs(Fr::MODULUS.0);
+    ensure!(
+        public_inputs
+            .iter()
+            .all(|input| U256::from_be_bytes(input.0) < modulus),
+        "PUBLIC_INPUT_GE_P"
+    );
+    let inputs = public_inputs.iter().map(fr).collect::<Vec<_>>();
+
+    // commitments are `y, x` words, reduced like the contract does
+    let word = |i: usize| &proof[i * 0x20..(i + 1) * 0x20];
+    let points =
+        [0, 2, 4, 6, 8, 10, 12, 14, 16, 18, 20, 63, 65].map(|i| (fq(word(i + 1)), fq(word(i))));
+    let e = (0..NUM_EVALUATIONS)
+        .map(|i| Fr::from_be_bytes_mod_order(word(22 + i)))
+        .collect::<Vec<_>>();
+    let commitment = |i: usize| -> Result<G1Affine> {
+        let point = G1Affine::new_unchecked(points[i].0, points[i].1);
+        ensure!(point.is_on_curve(), "POINT_NOT_ON_CURVE");
+        Ok(point)
+    };
+    let point_words = |range: std::ops::Range<usize>| {
+        range
+            .flat_map(|i| [words(&points[i].1), words(&points[i].0)])
+            .collect::<Vec<_>>()
+            .concat()
+    };
+
+    // challenges
+    let mut transcript = Transcript(keccak256(
+        [
+            CIRCUIT_SIZE.to_be_bytes(),
+            (NUM_INPUTS as u32).to_be_bytes(),
+        ]
+        .concat(),
+    ));
+    let eta = transcript.next(
+        &[
+            public_inputs.iter().flat_map(|input| input.0).collect(),
+            proof[..0xc0].to_vec(),
+        ]
+        .concat(),
+    );
+    let beta = transcript.next(&point_words(3..5));
+    let gamma = transcript.next(&[1]);
+    let alpha = transcript.next(&point_words(5..7));
+    let zeta = transcript.next(&point_words(7..11));
+
+    // public input delta, lagrange polynomials at ζ and the vanishing polynomial
+    let work_root = fr_hex(WORK_ROOT);
+    let work_root_inverse = fr_hex(WORK_ROOT_INVERSE);
+    let (mut numerator, mut denominator) = (Fr::one(), Fr::one());
+    let (mut root_1, mut root_2) = (beta * Fr::from(5), beta * Fr::from(12));
+    for input in &inputs {
+        numerator *= root_1 + input + gamma;
+        denominator *= root_2 + input + gamma;
+        root_1 *= work_root;
+        root_2 *= work_root;
+    }
+    let public_input_delta = numerator * inverse(denominator)?;
+
+    let beta_gamma = gamma * (beta + Fr::one());
+    let plookup_delta = pow_n(beta_gamma) * inverse(beta_gamma.pow([4]))?;
+
+    let zeta_pow_n = pow_n(zeta);
+    let vanishing_numerator = zeta_pow_n - Fr::one();
+    let vanishing_denominator = (1..=4)
+        .map(|i| zeta - work_root_inverse.pow([i]))
+        .product::<Fr>();
+    let zero_poly_inverse = vanishing_denominator * inverse(vanishing_numerator)?;
+    let lagrange_numerator = vanishing_numerator * fr_hex(DOMAIN_INVERSE);
+    let l_start = lagrange_numerator * inverse(zeta - Fr::one())?;
+    let l_end = lagrange_numerator * inverse(work_root.pow([5]) * zeta - Fr::one())?;
+
+    // widgets, each under the next powers of α
+    let mut alpha_base = alpha;
+
+    let permutation = {
+        let wire = |i: usize, sigma: usize| e[W1 + i] + gamma + beta * e[sigma + i];
+        let mut result = alpha_base * e[Z] * (0..4).map(|i| wire(i, ID1)).product::<Fr>()
+            - alpha_base * e[Z_OMEGA] * (0..4).map(|i| wire(i, SIGMA1)).product::<Fr>();
+        alpha_base *= alpha;
+        result += alpha_base * l_end * (e[Z_OMEGA] - public_input_delta);
+        alpha_base *= alpha;
+        result += alpha_base * l_start * (e[Z] - Fr::one());
+        alpha_base *= alpha;
+        result
+    };
+
+    let plookup = {
+        let f = ((eta * e[Q3] + e[W3] + e[QC] * e[W3_OMEGA]) * eta + e[W2] + e[QM] * e[W2_OMEGA])
+            * eta
+            + e[W1]
+            + e[Q2] * e[W1_OMEGA];
+        let table = |start: usize| {
+            (0..4)
+                .rev()
+                .fold(Fr::zero(), |table, i| table * eta + e[start + i])
+        };
+        let mut numerator = (f * e[TABLE_TYPE] + gamma)
+            * (table(TABLE1) + beta * table(TABLE1_OMEGA) + beta_gamma)
+            * (beta + Fr::one());
+        numerator = (numerator + alpha * l_start) * e[Z_LOOKUP] - alpha * l_start;
+        let l_end_alpha = alpha.square() * l_end;
+        let denominator = (e[S] + e[S_OMEGA] * beta + beta_gamma - l_end_alpha) * e[Z_LOOKUP_OMEGA]
+            + l_end_alpha * plookup_delta;
+        let result = (numerator - denominator) * alpha_base;
+        alpha_base *= alpha.pow([3]);
+        result
+    };
+
+    let arithmetic = {
+        let identity = e[QC] + (0..4).map(|i| e[W1 + i] * e[Q1 + i]).sum::<Fr>()
+            - e[W1] * e[W2] * e[QM] * (e[QARITH] - Fr::from(3)) * inverse(Fr::from(2))?;
+        let extra_small_addition =
+            alpha * (e[QARITH] - Fr::from(2)) * (e[QM] - e[W1_OMEGA] + e[W1] + e[W4]);
+        let result = alpha_base
+            * e[QARITH]
+            * (identity + (e[QARITH] - Fr::one()) * (e[W4_OMEGA] + extra_small_addition));
+        alpha_base *= alpha.square();
+        result
+    };
+
+    let sort = {
+        let deltas = [
+            e[W2] - e[W1],
+            e[W3] - e[W2],
+            e[W4] - e[W3],
+            e[W1_OMEGA] - e[W4],
+        ];
+        let result = deltas
+            .iter()
+            .enumerate()
+            .map(|(i, d)| {
+                (d.square() - d)
+                    * (*d - Fr::from(2))
+                    * (*d - Fr::from(3))
+                    * alpha_base
+                    * alpha.pow([i as u64])
+            })
+            .sum::<Fr>()
+            * e[QSORT];
+        alpha_base *= alpha.pow([4]);
+        result
+    };
+
+    let elliptic = {
+        let (x1, x2, x3) = (e[W2], e[W1_OMEGA], e[W2_OMEGA]);
+        let (y1, y2, y3) = (e[W3], e[W4_OMEGA], e[W3_OMEGA]);
+        let sign = e[Q1];
+        let x_diff = x2 - x1;
+        let y1y2 = y1 * y2 * sign;
+        let x_add = ((x3 + x2 + x1) * x_diff.square() - (y2.square() + y1.square())
+            + y1y2.double())
+            * (Fr::one() - e[QM])
+            * alpha_base;
+        let y_add = ((y1 + y3) * x_diff + (x3 - x1) * (y2 * sign - y1))
+            * (Fr::one() - e[QM])
+            * alpha_base
+            * alpha;
+
+        // doubling on Grumpkin, y² = x³ - 17
+        let x_pow_4 = (y1.square() + Fr::from(17)) * x1;
+        let x_double = ((x3 + x1 + x1) * y1.square() * Fr::from(4) - x_pow_4 * Fr::from(9))
+            * alpha_base
+            * e[QM];
+        let y_double = (x1.square() * Fr::from(3) * (x1 - x3) - y1.double() * (y1 + y3))
+            * alpha_base
+            * alpha
+            * e[QM];
+        alpha_base *= alpha.pow([4]);
+        (x_add + y_add + x_double + y_double) * e[QELLIPTIC]
+    };
+
+    let auxiliary = {
+        let limb_size = Fr::from(2).pow([68]);
+        let sublimb_shift = Fr::from(2).pow([14]);
+
+        let mut limb_subproduct = e[W1] * e[W2_OMEGA] + e[W1_OMEGA] * e[W2];
+        let non_native_field_2 = ((e[W1] * e[W4] + e[W2] * e[W3] - e[W3_OMEGA]) * limb_size
+            - e[W4_OMEGA]
+            + limb_subproduct)
+            * e[Q4];
+        limb_subproduct = limb_subproduct * limb_size + e[W1_OMEGA] * e[W2_OMEGA];
+        let non_native_field_1 = (limb_subproduct - (e[W3] + e[W4])) * e[Q3];
+        let non_native_field_3 = (limb_subproduct + e[W4] - (e[W3_OMEGA] + e[W4_OMEGA])) * e[QM];
+        let non_native_field =
+            (non_native_field_1 + non_native_field_2 + non_native_field_3) * e[Q2];
+
+        let accumulate = |limbs: [usize; 5]| {
+            limbs
+                .iter()
+                .fold(Fr::zero(), |acc, i| acc * sublimb_shift + e[*i])
+        };
+        let limb_accumulator = ((accumulate([W2_OMEGA, W1_OMEGA, W3, W2, W1]) - e[W4]) * e[Q4]
+            + (accumulate([W3_OMEGA, W2_OMEGA, W1_OMEGA, W4, W3]) - e[W4_OMEGA]) * e[QM])
+            * e[Q3];
+
+        let partial_record_check = ((e[W3] * eta + e[W2]) * eta + e[W1]) * eta + e[QC];
+        let memory_record_check = partial_record_check - e[W4];
+        let index_delta = e[W1_OMEGA] - e[W1];
+        let record_delta = e[W4_OMEGA] - e[W4];
+        let index_is_monotonically_increasing = index_delta.square() - index_delta;
+        let adjacent_values_match_if_adjacent_indices_match =
+            record_delta * (Fr::one() - index_delta);
+        let rom_consistency = (adjacent_values_match_if_adjacent_indices_match * alpha
+            + index_is_monotonically_increasing)
+            * alpha
+            + memory_record_check;
+
+        let next_gate_access_type =
+            e[W4_OMEGA] - ((e[W3_OMEGA] * eta + e[W2_OMEGA]) * eta + e[W1_OMEGA]) * eta;
+        let value_delta = e[W3_OMEGA] - e[W3];
+        let adjacent_values_match_if_next_access_is_a_read =
+            (Fr::one() - index_delta) * value_delta * (Fr::one() - next_gate_access_type);
+        let access_type = e[W4] - partial_record_check;
+        let ram_consistency = ((adjacent_values_match_if_next_access_is_a_read * alpha
+            + index_is_monotonically_increasing)
+            * alpha
+            + next_gate_access_type.square()
+            - next_gate_access_type)
+            * alpha
+            + access_type.square()
+            - access_type;
+        let ram_timestamp = (e[W2_OMEGA] - e[W2]) * (Fr::one() - index_delta) - e[W3];
+
+        let memory =
+            (rom_consistency * e[Q2] + ram_timestamp * e[Q4] + memory_record_check * e[QM]) * e[Q1]
+                + ram_consistency * e[QARITH];
+        (memory + non_native_field + limb_accumulator) * e[QAUX] * alpha_base
+    };
+
+    let quotient =
+        (permutation + plookup + arithmetic + sort + elliptic + auxiliary) * zero_poly_inverse;
+
+    // opening challenges, the last two being the same as in the contract
+    let nu = transcript.next(&[words(&quotient), proof[0x2c0..0x7e0].to_vec()].concat());
+    let mut v = vec![nu];
+    for i in 1..30u8 {
+        v.push(fr(&keccak256([transcript.0.as_slice(), &[i]].concat())));
+    }
+    transcript.0 = keccak256([transcript.0.as_slice(), &[29]].concat());
+    let u = transcript.next(&point_words(11..13));
+
+    // batch opening of every commitment, at ζ and at ζω for the shifted ones
+    let mut accumulator = G1Projective::from(commitment(T1)?);
+    for i in 1..4 {
+        accumulator += commitment(T1 + i)? * zeta_pow_n.pow([i as u64]);
+    }
+    let mut batch_evaluation = quotient;
+    for (i, v) in v.iter().enumerate() {
+        let shifted = match i {
+            0..=6 => Some(W1_OMEGA + i),
+            21..=24 => Some(TABLE1_OMEGA + i - TABLE1),
+            _ => None,
+        };
+        let point = match i {
+            0..=6 => commitment(i)?,
+            _ => vk_commitment(i - 7),
+        };
+        match shifted {
+            Some(j) => {
+                accumulator += point * (*v * (u + Fr::one()));
+                batch_evaluation += *v * (e[j] * u + e[i]);
+            }
+            None => {
+                accumulator += point * v;
+                batch_evaluation += *v * e[i];
+            }
+        }
+    }
+    accumulator -= G1Affine::generator() * batch_evaluation;
+
+    let (pi_z, pi_z_omega) = (commitment(PI_Z)?, commitment(PI_Z_OMEGA)?);
+    let rhs = accumulator + pi_z * zeta + pi_z_omega * (u * zeta * work_root);
+    let lhs = -(pi_z_omega * u + pi_z);
+
+    let g2_x = G2Affine::new_unchecked(
+        Fq2::new(fq_hex(G2_X[1]), fq_hex(G2_X[0])),
+        Fq2::new(fq_hex(G2_X[3]), fq_hex(G2_X[2])),
+    );
+    let pairing = Bn254::multi_pairing(
+        [rhs.into_affine(), lhs.into_affine()],
+        [G2Affine::generator(), g2_x],
+    );
+    if !pairing.0.is_one() {
+        bail!("PAIRING_FAILED");
+    }
+    Ok(())
+}
+
+/// Fiat-Shamir challenges, each hashing the previous one with the new data.
+struct Transcript(B256);
+
+impl Transcript {
+    fn next(&mut self, data: &[u8]) -> Fr {
+        self.0 = keccak256([self.0.as_slice(), data].concat());
+        fr(&self.0)
+    }
+}
+
+/// `x^n`, `n` being the circuit size.
+fn pow_n(x: Fr) -> Fr {
+    (0..CIRCUIT_SIZE.trailing_zeros()).fold(x, |x, _| x.square())
+}
+
+fn inverse(x: Fr) -> Result<Fr> {
+    x.inverse().ok_or_else(|| anyhow!("MOD_EXP_FAILURE"))
+}
+
+fn vk_commitment(i: usize) -> G1Affine {
+    G1Affine::new_unchecked(fq_hex(VK_COMMITMENTS[i][0]), fq_hex(VK_COMMITMENTS[i][1]))
+}
+
+fn fr(word: &B256) -> Fr {
+    Fr::from_be_bytes_mod_order(word.as_ref())
+}
+
+fn fq(word: &[u8]) -> Fq {
+    Fq::from_be_bytes_mod_order(word)
+}
+
+fn fr_hex(hex: &str) -> Fr {
+    Fr::from_be_bytes_mod_order(&hex::decode(hex).unwrap())
+}
+
+fn fq_hex(hex: &str) -> Fq {
+    Fq::from_be_bytes_mod_order(&hex::decode(hex).unwrap())
+}
+
+fn words<F: PrimeField>(value: &F) -> Vec<u8> {
+    value.into_bigint().to_bytes_be()
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::b256;
+
+    use super::*;
+
+    #[test]
+    fn verify_circuits_proof() {
+        let proof =
+            hex::decode(include_str!("../../../circuits/proofs/circuits.proof").trim()).unwrap();
+        // circuits/Verifier.toml
+        let mut inputs = [
+            b256!("1fc12a4421a52b9485cd696b8cfe29c1c3fcaf77f9fadccce0d47593301ad232"),
+            b256!("0dd32faaef5273e1fe22189585be98493fd3d2c2c6e61728dda32ec5335f3ce3"),
+            b256!("0000000000000000000000000000000000000000000000000000000000000001"),
+            b256!("25b481cf08c5d3aa7f05b7dd27d150b6ae920dea0f56622c867e8bc6544c50e1"),
+            b256!("00000000000000000000000000000000000000000000000000000000075bcd15"),
+            b256!("000000000000000000000000000000000000000000000000000000006673f6cc"),
+            b256!("156244c131d6f68246496786659cf2e7a1119e83b3e3d88173f1eb5ce3b110cf"),
+        ];
+        verify_proof(&proof, &inputs).unwrap();
+
+        assert!(verify_proof(&proof[..PROOF_SIZE - 1], &inputs).is_err());
+        assert!(verify_proof(&proof, &inputs[..6]).is_err());
+        let mut tampered = proof.clone();
+        tampered[0x2c0 + 31] ^= 1;
+        assert!(verify_proof(&tampered, &inputs).is_err());
+        inputs[2] = b256!("0000000000000000000000000000000000000000000000000000000000000002");
+        assert!(verify_proof(&proof, &inputs).is_err());
+
+        let verifier = include_str!("../../../contracts/src/Verifier.sol");
+        assert!(verifier.contains(&format!("return {};", VERIFICATION_KEY_HASH)));
+    }
+}
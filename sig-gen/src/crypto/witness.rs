@@ -0,0 +1,263 @@
+use std::{fmt::Write, str::FromStr};
+
+use alloy::primitives::{keccak256, Address, B256};
+use anyhow::{anyhow, bail, Result};
+use ark_bn254::Fr;
+use ark_ed_on_bn254::Fr as EdFr;
+use ark_ff::{BigInteger, PrimeField};
+use serde::Deserialize;
+
+use super::{convert, eddsa_verify, hash, EdAffine, Identity};
+
+/// Body of a `/signature` response, as far as the circuit needs it.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SignatureResponse {
+    pub signatures: Vec<RoleSignature>,
+    pub timestamp: u64,
+    /// Curia public key, missing from responses of older servers.
+    pub pubkey: Option<Pubkey>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct RoleSignature {
+    pub role: u8,
+    pub sig_rx: B256,
+    pub sig_ry: B256,
+    pub sig_s: B256,
+    pub random_nonce: B256,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct Pubkey {
+    pub x: B256,
+    pub y: B256,
+}
+
+impl From<Pubkey> for EdAffine {
+    fn from(pubkey: Pubkey) -> Self {
+        EdAffine::new_unchecked(field(&pubkey.x), field(&pubkey.y))
+    }
+}
+
+/// Inputs of the `main` circuit of `circuits`.
+#[derive(Debug, Clone)]
+pub struct Witness {
+    pub address: Address,
+    pub sig_r: EdAffine,
+    pub sig_s: EdFr,
+    pub random_nonce: Fr,
+    pub revoker_secret: Fr,
+    pub pubkey: EdAffine,
+    pub role: u8,
+    /// `keccak256(message) mod p`, the message being the attestation's
+    /// title and message concatenated.
+    pub msg: Fr,
+    pub nonce: Fr,
+    pub timestamp: u64,
+}
+
+impl Witness {
+    /// Witness for the signature of `role` in `response`, checked against
+    /// `pubkey` so a wrong address or key fails here rather than in the prover.
+    pub fn new(
+        response: &SignatureResponse,
+        role: u8,
+        address: Address,
+        pubkey: EdAffine,
+        revoker_secret: Fr,
+        message: &[u8],
+        nonce: Fr,
+    ) -> Result<Self> {
+        let signature = response
+            .signatures
+            .iter()
+            .find(|signature| signature.role == role)
+            .ok_or_else(|| anyhow!("No signature for role {}", role))?;
+
+        let witness = Witness {
+            address,
+            sig_r: EdAffine::new_unchecked(field(&signature.sig_rx), field(&signature.sig_ry)),
+            sig_s: EdFr::from_be_bytes_mod_order(signature.sig_s.as_ref()),
+            random_nonce: field(&signature.random_nonce),
+            revoker_secret,
+            pubkey,
+            role,
+            msg: Fr::from_be_bytes_mod_order(keccak256(message).as_ref()),
+            nonce,
+            timestamp: response.timestamp,
+        };
+        if !witness.pubkey.is_on_curve() || !witness.sig_r.is_on_curve() {
+            bail!("Public key or signature is not on the curve");
+        }
+        let identity = Identity {
+            address,
+            role,
+            timestamp: witness.timestamp,
+            random_nonce: witness.random_nonce,
+        }
+        .hash()?;
+        if !eddsa_verify(witness.pubkey, identity, witness.sig_r, witness.sig_s)? {
+            bail!("Signature does not match the address and public key");
+        }
+
+        Ok(witness)
+    }
+
+    /// Public output of the circuit, `H(H(timestamp, secret), H(timestamp, secret))`.
+    pub fn revoker_hash(&self) -> Result<Fr> {
+        let revoker = hash(&[Fr::from(self.timestamp), self.revoker_secret])?;
+        hash(&[revoker, revoker])
+    }
+
+    /// Inputs of `nargo prove`, decimal.
+    pub fn prover_toml(&self) -> String {
+        let inputs = vec![
+            ("address", decimal(&address_field(self.address))),
+            ("msg", decimal(&self.msg)),
+            ("nonce", decimal(&self.nonce)),
+            ("random_nonce", decimal(&self.random_nonce)),
+            ("revoker_secret", decimal(&self.revoker_secret)),
+            ("role", self.role.to_string()),
+            ("sig_s", decimal(&self.sig_s)),
+            ("timestamp", self.timestamp.to_string()),
+        ];
+        toml(
+            inputs,
+            &[("pubkey", self.pubkey), ("sig_r", self.sig_r)],
+            decimal,
+        )
+    }
+
+    /// Public inputs and output checked by `nargo verify`, as 32-byte hex.
+    pub fn verifier_toml(&self) -> Result<String> {
+        let inputs = vec![
+            ("msg", word(&self.msg)),
+            ("nonce", word(&self.nonce)),
+            ("return", word(&self.revoker_hash()?)),
+            ("role", word(&Fr::from(self.role))),
+            ("timestamp", word(&Fr::from(self.timestamp))),
+        ];
+        Ok(toml(inputs, &[("pubkey", self.pubkey)], word))
+    }
+}
+
+/// Parse a field element given in decimal or as `0x` hex.
+pub fn parse_field(s: &str) -> Result<Fr> {
+    match s.strip_prefix("0x") {
+        Some(hex) => Ok(Fr::from_be_bytes_mod_order(&hex::decode(hex)?)),
+        None => Fr::from_str(s).map_err(|_| anyhow!("Invalid field element {}", s)),
+    }
+}
+
+fn field(bytes: &B256) -> Fr {
+    Fr::from_be_bytes_mod_order(bytes.as_ref())
+}
+
+fn address_field(address: Address) -> Fr {
+    Fr::from_be_bytes_mod_order(address.as_ref())
+}
+
+fn decimal<F: PrimeField>(value: &F) -> String {
+    value.into_bigint().to_string()
+}
+
+fn word<F: PrimeField>(value: &F) -> String {
+    format!("0x{}", hex::encode(value.into_bigint().to_bytes_be()))
+}
+
+/// Render keys sorted, then a table for every point.
+fn toml(
+    mut inputs: Vec<(&str, String)>,
+    points: &[(&str, EdAffine)],
+    format: fn(&Fr) -> String,
+) -> String {
+    inputs.sort();
+    let mut toml = String::new();
+    for (key, value) in inputs {
+        writeln!(toml, "{} = \"{}\"", key, value).unwrap();
+    }
+    for (table, point) in points {
+        writeln!(toml, "\n[{}]", table).unwrap();
+        writeln!(toml, "x = \"{}\"", format(&convert(&point.x))).unwrap();
+        writeln!(toml, "y = \"{}\"", format(&convert(&point.y))).unwrap();
+    }
+    toml
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use alloy::primitives::address;
+    use serde_json::json;
+
+    use super::*;
+
+    /// `key = value` lines, prefixed by their table.
+    fn entries(toml: &str) -> BTreeMap<String, String> {
+        let mut table = String::new();
+        let mut entries = BTreeMap::new();
+        for line in toml.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            if let Some(name) = line.strip_prefix('[') {
+                table = format!("{}.", name.trim_end_matches(']'));
+            } else if let Some((key, value)) = line.split_once(" = ") {
+                entries.insert(format!("{}{}", table, key), value.to_string());
+            }
+        }
+        entries
+    }
+
+    fn witness(sig: [&str; 3]) -> Result<Witness> {
+        let response: SignatureResponse = serde_json::from_value(json!({
+            "signatures": [{
+                "role": 1,
+                "sig_rx": word(&Fr::from_str(sig[0]).unwrap()),
+                "sig_ry": word(&Fr::from_str(sig[1]).unwrap()),
+                "sig_s": word(&Fr::from_str(sig[2]).unwrap()),
+                "random_nonce": word(&Fr::from(123456789000u64)),
+            }],
+            "timestamp": 1718875852,
+            "pubkey": {
+                "x": "0x1fc12a4421a52b9485cd696b8cfe29c1c3fcaf77f9fadccce0d47593301ad232",
+                "y": "0x0dd32faaef5273e1fe22189585be98493fd3d2c2c6e61728dda32ec5335f3ce3",
+            },
+        }))?;
+
+        Witness::new(
+            &response,
+            1,
+            address!("000000000000000000000000000000000000dEaD"),
+            response.pubkey.unwrap().into(),
+            parse_field("126879297332596")?,
+            b"Hello, world!",
+            parse_field("0x075bcd15")?,
+        )
+    }
+
+    #[test]
+    fn test_main_vectors() -> Result<()> {
+        // circuits/src/main.nr
+        let legacy = witness([
+            "9511822940976101281659138595606619745991906839893458237629594161066286490837",
+            "18714767357424465254042961065908942228149413171081771799954627430213815576545",
+            "1084962730721589343861882407938283729631298378329681939209113546914067968972",
+        ])?;
+        assert_eq!(
+            legacy.revoker_hash()?,
+            Fr::from_str(
+                "9672195359866897248631522186500336244216588785587913256300043371390186885327"
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            entries(&legacy.prover_toml()),
+            entries(include_str!("../../../circuits/Prover.toml"))
+        );
+        assert_eq!(
+            legacy.verifier_toml()?,
+            include_str!("../../../circuits/Verifier.toml")
+        );
+
+        Ok(())
+    }
+}
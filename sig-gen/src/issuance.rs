@@ -0,0 +1,338 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    mem::take,
+    path::Path,
+    sync::Arc,
+};
+
+use alloy::primitives::Address;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::{query::SNAPSHOT_ROLE, store::JsonLog};
+
+/// Signatures issued for one role during one epoch. Roles held at a snapshot
+/// have the snapshot's time as epoch, see `EpochKey::is_snapshot`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct EpochKey {
+    pub chain_id: u64,
+    pub epoch: u64,
+    pub role: u8,
+}
+
+impl EpochKey {
+    /// Whether the key counts signatures of a role held at a snapshot. These
+    /// never close, so the quota of a snapshot holds for good.
+    pub fn is_snapshot(&self) -> bool {
+        self.role & SNAPSHOT_ROLE != 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EpochStats {
+    pub signatures: u64,
+    pub addresses: u64,
+}
+
+impl EpochStats {
+    fn of(addresses: &HashMap<Address, u64>) -> Self {
+        EpochStats {
+            signatures: addresses.values().sum(),
+            addresses: addresses.values().filter(|n| **n > 0).count() as u64,
+        }
+    }
+
+    fn add(&mut self, other: EpochStats) {
+        self.signatures += other.signatures;
+        self.addresses += other.addresses;
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum IssuanceRecord {
+    Issued {
+        #[serde(flatten)]
+        key: EpochKey,
+        address: Address,
+    },
+    /// Totals of a closed epoch, replacing its `Issued` records.
+    Closed {
+        #[serde(flatten)]
+        key: EpochKey,
+        #[serde(flatten)]
+        stats: EpochStats,
+    },
+}
+
+#[derive(Debug, Default)]
+struct Epochs {
+    /// Signatures per address, only kept while the epoch is open.
+    open: BTreeMap<EpochKey, HashMap<Address, u64>>,
+    closed: BTreeMap<EpochKey, EpochStats>,
+}
+
+impl Epochs {
+    /// Replace the addresses of every epoch before `epoch` by their totals,
+    /// snapshots aside.
+    fn close(&mut self, epoch: u64) {
+        let (closed, open) = take(&mut self.open)
+            .into_iter()
+            .partition::<BTreeMap<_, _>, _>(|(key, _)| !key.is_snapshot() && key.epoch < epoch);
+        self.open = open;
+        for (key, addresses) in closed {
+            self.closed
+                .entry(key)
+                .or_default()
+                .add(EpochStats::of(&addresses));
+        }
+    }
+
+    /// Log records holding the same counters.
+    fn records(&self) -> Vec<IssuanceRecord> {
+        let closed = self
+            .closed
+            .iter()
+            .map(|(key, stats)| IssuanceRecord::Closed {
+                key: *key,
+                stats: *stats,
+            });
+        let open = self.open.iter().flat_map(|(key, addresses)| {
+            addresses.iter().flat_map(|(address, n)| {
+                (0..*n).map(|_| IssuanceRecord::Issued {
+                    key: *key,
+                    address: *address,
+                })
+            })
+        });
+        closed.chain(open).collect()
+    }
+}
+
+/// Tracks signature issuance per epoch. Signed timestamps are rounded down to
+/// the start of their epoch, so everyone signed for a role within the same
+/// epoch shares one anonymity set.
+///
+/// Once a later epoch starts, the addresses of the previous ones are dropped
+/// and only their totals are kept, in memory and in the log. Snapshots keep
+/// their addresses.
+#[derive(Debug, Clone)]
+pub struct Issuance {
+    pub epoch_seconds: u64,
+    epochs: Arc<RwLock<Epochs>>,
+    log: JsonLog<IssuanceRecord>,
+}
+
+impl Issuance {
+    /// Open the issuance log at `path` and replay the counters it holds.
+    pub async fn open(epoch_seconds: u64, path: impl AsRef<Path>) -> Result<Self> {
+        let (log, records) = JsonLog::<IssuanceRecord>::open(path).await?;
+        let mut epochs = Epochs::default();
+        for record in records {
+            match record {
+                IssuanceRecord::Issued { key, address } => {
+                    *epochs
+                        .open
+                        .entry(key)
+                        .or_default()
+                        .entry(address)
+                        .or_default() += 1
+                }
+                IssuanceRecord::Closed { key, stats } => {
+                    epochs.closed.entry(key).or_default().add(stats)
+                }
+            }
+        }
+
+        Ok(Issuance {
+            epoch_seconds: epoch_seconds.max(1),
+            epochs: Arc::new(RwLock::new(epochs)),
+            log,
+        })
+    }
+
+    /// Start of the epoch containing `timestamp`.
+    pub fn epoch(&self, timestamp: u64) -> u64 {
+        timestamp - timestamp % self.epoch_seconds
+    }
+
+    /// Record a signature for `address` unless it already received `quota`
+    /// signatures for the same role and epoch. Returns whether it was recorded.
+    /// The first signature of a new epoch closes the previous ones.
+    pub async fn issue(&self, key: EpochKey, address: Address, quota: Option<u64>) -> Result<bool> {
+        let mut epochs = self.epochs.write().await;
+        if !key.is_snapshot()
+            && epochs
+                .open
+                .keys()
+                .any(|open| !open.is_snapshot() && open.epoch < key.epoch)
+        {
+            epochs.close(key.epoch);
+            self.log.rewrite(&epochs.records()).await?;
+        }
+
+        let issued = epochs
+            .open
+            .entry(key)
+            .or_default()
+            .entry(address)
+            .or_default();
+        if quota.is_some_and(|quota| *issued >= quota) {
+            return Ok(false);
+        }
+
+        // persist before counting, so a restart never hands out more than the quota
+        self.log
+            .append(&IssuanceRecord::Issued { key, address })
+            .await?;
+        *issued += 1;
+        Ok(true)
+    }
+
+    /// Issuance of every role of a chain during the epoch starting at
+    /// `epoch`. Snapshot roles count the signatures for the snapshots taken
+    /// during the epoch.
+    pub async fn stats(&self, chain_id: u64, epoch: u64) -> BTreeMap<u8, EpochStats> {
+        let from = EpochKey {
+            chain_id,
+            epoch,
+            role: u8::MIN,
+        };
+        let to = EpochKey {
+            epoch: epoch + self.epoch_seconds - 1,
+            role: u8::MAX,
+            ..from
+        };
+
+        let epochs = self.epochs.read().await;
+        let mut stats = BTreeMap::<u8, EpochStats>::new();
+        for (key, closed) in epochs.closed.range(from..=to) {
+            stats.entry(key.role).or_default().add(*closed);
+        }
+        for (key, addresses) in epochs.open.range(from..=to) {
+            stats
+                .entry(key.role)
+                .or_default()
+                .add(EpochStats::of(addresses));
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+
+    use alloy::primitives::address;
+    use anyhow::Result;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn quota_survives_restart() -> Result<()> {
+        let path = temp_dir().join(format!("issuance-{}.jsonl", std::process::id()));
+        let address = address!("000000000000000000000000000000000000dEaD");
+        let issuance = Issuance::open(3600, &path).await?;
+        let key = EpochKey {
+            chain_id: 10,
+            epoch: issuance.epoch(1718875852),
+            role: 1,
+        };
+        assert_eq!(key.epoch, 1718874000);
+
+        assert!(issuance.issue(key, address, Some(2)).await?);
+        assert!(issuance.issue(key, address, Some(2)).await?);
+        assert!(!issuance.issue(key, address, Some(2)).await?);
+        assert!(issuance.issue(key, Address::ZERO, Some(2)).await?);
+
+        let reopened = Issuance::open(3600, &path).await?;
+        assert!(!reopened.issue(key, address, Some(2)).await?);
+        assert_eq!(
+            reopened.stats(10, key.epoch).await.get(&1),
+            Some(&EpochStats {
+                signatures: 3,
+                addresses: 2
+            })
+        );
+
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn closed_epochs_keep_totals() -> Result<()> {
+        let path = temp_dir().join(format!("issuance-closed-{}.jsonl", std::process::id()));
+        let address = address!("000000000000000000000000000000000000dEaD");
+        let issuance = Issuance::open(3600, &path).await?;
+        let key = EpochKey {
+            chain_id: 10,
+            epoch: 1718874000,
+            role: 1,
+        };
+        let next = EpochKey {
+            epoch: key.epoch + 3600,
+            ..key
+        };
+        let totals = EpochStats {
+            signatures: 3,
+            addresses: 2,
+        };
+
+        assert!(issuance.issue(key, address, None).await?);
+        assert!(issuance.issue(key, address, None).await?);
+        assert!(issuance.issue(key, Address::ZERO, None).await?);
+        assert!(issuance.issue(next, address, None).await?);
+        assert_eq!(issuance.stats(10, key.epoch).await.get(&1), Some(&totals));
+
+        let log = std::fs::read_to_string(&path)?;
+        assert_eq!(log.lines().count(), 2);
+        assert_eq!(log.matches("address\":\"0x").count(), 1);
+
+        let reopened = Issuance::open(3600, &path).await?;
+        assert_eq!(reopened.stats(10, key.epoch).await.get(&1), Some(&totals));
+        assert!(!reopened.issue(next, address, Some(1)).await?);
+
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn snapshot_quota_outlives_epochs() -> Result<()> {
+        let path = temp_dir().join(format!("issuance-snapshot-{}.jsonl", std::process::id()));
+        let address = address!("000000000000000000000000000000000000dEaD");
+        let issuance = Issuance::open(3600, &path).await?;
+        // requested in an epoch, then in a later one
+        let snapshot = EpochKey {
+            chain_id: 10,
+            epoch: 1718870123,
+            role: 1 | SNAPSHOT_ROLE,
+        };
+        let live = |epoch| EpochKey {
+            chain_id: 10,
+            epoch,
+            role: 1,
+        };
+
+        assert!(issuance.issue(live(1718874000), address, Some(1)).await?);
+        assert!(issuance.issue(snapshot, address, Some(1)).await?);
+        assert!(issuance.issue(live(1718877600), address, Some(1)).await?);
+        assert!(!issuance.issue(snapshot, address, Some(1)).await?);
+
+        let reopened = Issuance::open(3600, &path).await?;
+        assert!(!reopened.issue(snapshot, address, Some(1)).await?);
+        assert_eq!(
+            reopened
+                .stats(10, 1718866800)
+                .await
+                .get(&(1 | SNAPSHOT_ROLE)),
+            Some(&EpochStats {
+                signatures: 1,
+                addresses: 1
+            })
+        );
+
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+}
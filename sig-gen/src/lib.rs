@@ -1,3 +1,9 @@
 pub mod api;
+pub mod config;
+pub mod contracts;
 pub mod crypto;
+pub mod issuance;
+pub mod policy;
 pub mod query;
+pub mod relayer;
+pub mod store;
@@ -1,92 +1,295 @@
 use std::{
-    env::var,
+    collections::HashMap,
+    env::{args, var},
     future::IntoFuture,
     net::{Ipv4Addr, SocketAddrV4},
+    sync::Arc,
 };
 
 use alloy::{
-    hex::FromHex,
-    primitives::{Address, B256},
+    primitives::B256,
     providers::{Provider, ProviderBuilder},
     rpc::types::{BlockId, BlockTransactionsKind},
+    signers::local::PrivateKeySigner,
 };
-use anyhow::Result;
+use anyhow::{anyhow, bail, Context, Result};
 use ark_ec::{AffineRepr, CurveGroup};
 use ark_ed_on_bn254::Fr as EdFr;
 use ark_ff::{BigInteger, PrimeField};
 use axum::{routing::get, Router};
+use futures::future::select_all;
 use hyper::{header::CONTENT_TYPE, Method};
 use reqwest::Url;
 use sig_gen::{
-    api::{router, State},
-    crypto::EdAffine,
-    query::RoleQuerier,
+    api::{router, Challenges, Network, State},
+    config::Config,
+    contracts::{check_attester, AttesterCheck, AttesterClient, StartupCheck},
+    crypto::{pubkey_words, EdAffine},
+    issuance::Issuance,
+    query::{AttestationRole, DelegateIndex, DelegateTierRole, RoleRegistry, TokenRole},
+    relayer::{compact_jobs, open_jobs, Gate, RelayerPool, UserOpRelayer},
 };
 use tokio::{net::TcpListener, select};
 use tower_http::cors::{Any, CorsLayer};
-use tracing::info;
+use tracing::{error, info, warn};
+
+mod cli;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     dotenvy::dotenv().ok();
 
-    let provider = ProviderBuilder::new().on_http(Url::parse(&var("NODE_URL")?)?);
-    let testnet_provider = var("TESTNET_NODE_URL")
-        .ok()
-        .map(|url| Some(ProviderBuilder::new().on_http(Url::parse(&url).ok()?)))
-        .flatten();
-    let testnet_anonymous_attestator = var("TESTNET_ANONYMOUS_ATTESTOR")
-        .ok()
-        .map(|addr| Address::from_hex(&addr).ok())
-        .flatten();
-    let testnet_pubkey_registry = var("TESTNET_KEY_REGISTRY")
-        .ok()
-        .map(|addr| Address::from_hex(&addr).ok())
-        .flatten();
+    let args = args().skip(1).collect::<Vec<_>>();
+    if let Some((command, args)) = args.split_first() {
+        return cli::run(command, args).await;
+    }
+
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::DEBUG)
+        .init();
+
+    let config = Config::load(var("CONFIG_PATH").unwrap_or("config.json".to_string()))?;
     let private_key = EdFr::from_be_bytes_mod_order(&hex::decode(&var("PRIVATE_KEY")?)?);
     let public_key = (EdAffine::generator() * private_key).into_affine();
-    let pubkey_registry = Address::from_hex(&var("PUBKEY_REGISTRY")?)?;
-    let anonymous_attestator = Address::from_hex(&var("ANONYMOUS_ATTESTOR")?)?;
-    let proxy_private_key = B256::from_slice(&hex::decode(&var("PROXY_PRIVATE_KEY")?)?);
-
-    if let Some(block) = provider
-        .get_block(BlockId::latest(), BlockTransactionsKind::Hashes)
-        .await?
-    {
-        info!(
-            "Latest block {} at {}",
-            block.header.number.unwrap(),
-            block.header.timestamp
+    // comma separated keys of the relayer pool
+    let proxy_signers = var("PROXY_PRIVATE_KEY")?
+        .split(',')
+        .map(|key| -> Result<PrivateKeySigner> {
+            Ok(PrivateKeySigner::from_bytes(&B256::from_slice(
+                &hex::decode(key.trim())?,
+            ))?)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let challenges = Challenges::new(
+        var("SIWE_DOMAIN")?,
+        var("SIWE_NONCE_TTL")
+            .ok()
+            .map(|ttl| ttl.parse().ok())
+            .flatten()
+            .unwrap_or(300),
+    );
+
+    let issuance =
+        Issuance::open(config.epoch_seconds, config.data_dir.join("issuance.jsonl")).await?;
+    let (relay_log, relay_jobs) = open_jobs(config.data_dir.join("relayer.jsonl")).await?;
+
+    let rpc_urls = config
+        .networks
+        .iter()
+        .map(|network| (network.chain_id, network.rpc_url.clone()))
+        .collect::<HashMap<_, _>>();
+    let mut networks = HashMap::new();
+    let mut pollers = vec![];
+    for network in config.networks {
+        let provider = ProviderBuilder::new().on_http(Url::parse(&network.rpc_url)?);
+        let chain_id = provider.get_chain_id().await?;
+        if chain_id != network.chain_id {
+            bail!(
+                "RPC of network {} reports chain {}",
+                network.chain_id,
+                chain_id
+            );
+        }
+
+        if let Some(block) = provider
+            .get_block(BlockId::latest(), BlockTransactionsKind::Hashes)
+            .await?
+        {
+            info!(
+                "Chain {}: latest block {} at {}",
+                chain_id,
+                block.header.number.unwrap(),
+                block.header.timestamp
+            );
+        }
+
+        let AttesterCheck {
+            issues,
+            native_proofs,
+        } = check_attester(
+            &AttesterClient::new(network.anonymous_attestator, provider.clone()),
+            pubkey_words(&public_key),
+            network.schema,
+        )
+        .await;
+        match native_proofs {
+            true => info!("Chain {}: verifying proofs before relaying", chain_id),
+            false => warn!(
+                "Chain {}: verification key of the verifier unknown, proofs are only simulated",
+                chain_id
+            ),
+        }
+        for issue in &issues {
+            error!(
+                "Chain {}: AnonymousAttester {}: {}",
+                chain_id, network.anonymous_attestator, issue
+            );
+        }
+        if !issues.is_empty() && config.startup_check == StartupCheck::Strict {
+            bail!(
+                "Chain {}: AnonymousAttester {} does not match the configuration",
+                chain_id,
+                network.anonymous_attestator
+            );
+        }
+
+        let (mut roles, poller) =
+            RoleRegistry::optimism(provider.clone(), network.op_token, &network.eas_graphql)
+                .await?;
+        pollers.push(poller);
+        for role in network.attestation_roles {
+            info!(
+                "Chain {}: role {} ({}) for recipients of schema {}",
+                chain_id, role.role, role.id, role.schema
+            );
+            let (role, poller) = AttestationRole::new(role, &network.eas_graphql)
+                .await
+                .with_context(|| format!("Chain {}", chain_id))?;
+            roles
+                .register(role)
+                .with_context(|| format!("Chain {}", chain_id))?;
+            pollers.push(poller);
+        }
+        if let Some(delegate_index) = network
+            .delegate_index
+            .filter(|_| !network.delegate_tiers.is_empty())
+        {
+            let (index, poller) = DelegateIndex::new(
+                provider.clone(),
+                network.op_token,
+                delegate_index,
+                config
+                    .data_dir
+                    .join(format!("delegates-{}.jsonl", chain_id)),
+            )
+            .await
+            .with_context(|| format!("Chain {}: failed to index the delegates", chain_id))?;
+            pollers.push(poller);
+            for role in network.delegate_tiers {
+                info!(
+                    "Chain {}: role {} ({}) for delegates {:?}",
+                    chain_id, role.role, role.id, role.tier
+                );
+                roles
+                    .register(DelegateTierRole {
+                        config: role,
+                        index: index.clone(),
+                    })
+                    .with_context(|| format!("Chain {}", chain_id))?;
+            }
+        }
+        for role in network.token_roles {
+            let token_chain_id = role.chain_id.unwrap_or(chain_id);
+            let rpc_url = role
+                .rpc_url
+                .as_ref()
+                .or(rpc_urls.get(&token_chain_id))
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Chain {}: role {} needs an rpc_url for chain {}",
+                        chain_id,
+                        role.id,
+                        token_chain_id
+                    )
+                })?;
+            let token_provider = ProviderBuilder::new().on_http(Url::parse(rpc_url)?);
+            if token_provider.get_chain_id().await? != token_chain_id {
+                bail!(
+                    "Chain {}: RPC of role {} is not on chain {}",
+                    chain_id,
+                    role.id,
+                    token_chain_id
+                );
+            }
+            info!(
+                "Chain {}: role {} ({}) for holders of {} on chain {}",
+                chain_id, role.role, role.id, role.contract, token_chain_id
+            );
+            roles
+                .register(TokenRole::new(role, chain_id, token_provider))
+                .with_context(|| format!("Chain {}", chain_id))?;
+        }
+        let (relayer, workers) = RelayerPool::new(
+            chain_id,
+            provider.clone(),
+            proxy_signers.clone(),
+            &relay_jobs,
+            relay_log.clone(),
+            config.relayer.clone(),
+            network.spending.clone(),
+        )?;
+        pollers.extend(workers);
+        let user_ops = match network.user_ops {
+            Some(user_ops) => {
+                let user_ops = UserOpRelayer::new(
+                    chain_id,
+                    user_ops,
+                    network.spending,
+                    PrivateKeySigner::from_bytes(&B256::from_slice(&hex::decode(&var(
+                        "USER_OP_OWNER_KEY",
+                    )?)?))?,
+                    PrivateKeySigner::from_bytes(&B256::from_slice(&hex::decode(&var(
+                        "PAYMASTER_SIGNER_KEY",
+                    )?)?))?,
+                    config.data_dir.join(format!("user-ops-{}.jsonl", chain_id)),
+                )
+                .await?;
+                pollers.push(user_ops.guard.watch_deposit(
+                    chain_id,
+                    provider.clone(),
+                    user_ops.config.entry_point,
+                    user_ops.config.paymaster,
+                ));
+                Some(user_ops)
+            }
+            None => None,
+        };
+        networks.insert(
+            chain_id,
+            Network {
+                chain_id,
+                provider,
+                roles,
+                relayer,
+                user_ops,
+                pubkey_registry: network.pubkey_registry,
+                anonymous_attestator: network.anonymous_attestator,
+                native_proofs,
+                issues,
+            },
         );
     }
 
-    let (role_querier, poller) = RoleQuerier::new().await?;
+    let others = relay_jobs
+        .into_iter()
+        .filter(|job| !networks.contains_key(&job.chain_id))
+        .collect();
+    let pools = networks
+        .values()
+        .map(|network| network.relayer.clone())
+        .collect();
+    pollers.push(compact_jobs(pools, others, relay_log));
 
+    let networks = Arc::new(networks);
     let app = Router::new()
         .nest("/", router())
         .with_state(State {
-            provider,
-            testnet_provider,
-            querier: role_querier,
+            networks: networks.clone(),
+            default_chain_id: config.default_chain_id,
             private_key,
-            pubkey_registry,
-            anonymous_attestator,
-            proxy_private_key,
-            testnet_pubkey_registry,
-            testnet_anonymous_attestator,
+            challenges,
+            issuance,
+            policy: config.policy,
+            gate: Gate::new(config.gate),
         })
         .layer(
             CorsLayer::new()
                 .allow_origin(Any)
-                .allow_methods([Method::POST])
+                .allow_methods([Method::GET, Method::POST])
                 .allow_headers([CONTENT_TYPE]),
         )
         .route("/", get(|| async { "Hello, World!" }));
 
-    tracing_subscriber::fmt()
-        .with_max_level(tracing::Level::DEBUG)
-        .init();
-
     let port = var("PORT")
         .ok()
         .map(|p| p.parse().ok())
@@ -94,8 +297,24 @@ async fn main() -> Result<()> {
         .unwrap_or(3010);
 
     info!("Starting server in port {}", port);
-    info!("Pubkey Registry at {}", pubkey_registry);
-    info!("Anonymous Attestator at {}", anonymous_attestator);
+    for network in networks.values() {
+        info!(
+            "Chain {}: Pubkey Registry at {}, Anonymous Attestator at {}",
+            network.chain_id, network.pubkey_registry, network.anonymous_attestator
+        );
+        for relayer in &network.relayer.relayers {
+            info!(
+                "Chain {}: relaying from {}",
+                network.chain_id, relayer.address
+            );
+        }
+        if let Some(user_ops) = &network.user_ops {
+            info!(
+                "Chain {}: user operations from {} through {}",
+                network.chain_id, user_ops.config.account, user_ops.config.bundler_url
+            );
+        }
+    }
     info!(
         "Public Key: ({}, {})",
         hex::encode(public_key.x.into_bigint().to_bytes_be()),
@@ -106,7 +325,7 @@ async fn main() -> Result<()> {
             TcpListener::bind(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port)).await?,
             app,
         ).into_future() => {}
-        _ = poller => {}
+        _ = select_all(pollers) => {}
     };
 
     Ok(())
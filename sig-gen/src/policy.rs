@@ -0,0 +1,20 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+/// Issuance policy for role signatures.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Policy {
+    /// Signatures an address may obtain per role and epoch, unlimited when unset.
+    #[serde(default)]
+    pub quota: Option<u64>,
+    /// Per-role overrides of `quota`, keyed by role number.
+    #[serde(default)]
+    pub role_quotas: HashMap<u8, u64>,
+}
+
+impl Policy {
+    pub fn quota(&self, role: u8) -> Option<u64> {
+        self.role_quotas.get(&role).copied().or(self.quota)
+    }
+}
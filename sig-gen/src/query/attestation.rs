@@ -1,43 +1,472 @@
+use std::{
+    cmp::Ordering,
+    collections::HashSet,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
 use alloy::{
-    dyn_abi::SolType,
-    hex::FromHex,
-    primitives::{Address, Bytes},
+    dyn_abi::{DynSolType, DynSolValue},
+    primitives::{hex, Address, Bytes, U256},
 };
-use serde::{de::Error as DeError, Deserialize, Deserializer};
+use anyhow::{anyhow, bail, Context, Result};
+use futures::{future::BoxFuture, FutureExt};
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::{from_value, json, Value};
+use tokio::{
+    spawn,
+    sync::RwLock,
+    task::JoinHandle,
+    time::{interval, Duration},
+};
+use tracing::{error, info};
+
+use super::{RoleProvider, Snapshot};
+use crate::config::{AttestationRoleConfig, Condition, RevocationPolicy};
 
-use super::Schema;
+const PAGE_SIZE: usize = 1000;
 
-#[derive(Debug, Deserialize)]
-pub struct BadgeholderAttestation {
+/// Attestation of a schema, as returned by the EAS GraphQL endpoint.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EasAttestation {
     pub recipient: Address,
-    #[serde(deserialize_with = "data_from_string")]
-    pub data: BadgeholderAttestationData,
-}
-
-#[derive(Debug, Deserialize)]
-pub struct BadgeholderAttestationData {
-    pub rpgf_round: u64,
-    pub referred_by: Address,
-    pub referred_method: String,
-}
-
-fn data_from_string<'de, D>(deserializer: D) -> Result<BadgeholderAttestationData, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    String::deserialize(deserializer).and_then(|s| {
-        let schema = Schema::abi_decode_sequence(
-            &Bytes::from_hex(s).map_err(|_| DeError::custom("failed to decode hex"))?,
-            false,
-        )
-        .map_err(|e| DeError::custom(format!("failed to decode schema: {}", e)))?;
-        Result::Ok(BadgeholderAttestationData {
-            rpgf_round: schema
-                .rpgfRound
-                .parse()
-                .map_err(|_| DeError::custom("failed to parse rpgf_round"))?,
-            referred_by: schema.referredBy,
-            referred_method: schema.referredMethod,
-        })
+    pub data: Bytes,
+    pub time_created: u64,
+    pub revoked: bool,
+    pub revocation_time: u64,
+    /// 0 when the attestation does not expire.
+    pub expiration_time: u64,
+}
+
+impl EasAttestation {
+    fn revoked_at(&self, time: u64) -> bool {
+        self.revoked && self.revocation_time <= time
+    }
+}
+
+/// Attestations of a role's schema, revoked ones included, and the
+/// recipients currently selected.
+#[derive(Debug, Clone, Default)]
+pub struct Attestations {
+    pub schema: String,
+    pub attestations: Vec<EasAttestation>,
+    pub recipients: HashSet<Address>,
+}
+
+/// Recipients of the attestations selected by an [`AttestationRoleConfig`].
+#[derive(Debug, Clone)]
+pub struct AttestationRole {
+    pub config: AttestationRoleConfig,
+    pub attestations: Arc<RwLock<Attestations>>,
+}
+
+impl AttestationRole {
+    /// Fetch the recipients from the role's EAS GraphQL endpoint, or else
+    /// `eas_graphql`, and again every `refresh_seconds`.
+    pub async fn new(
+        config: AttestationRoleConfig,
+        eas_graphql: &str,
+    ) -> Result<(Self, JoinHandle<()>)> {
+        let eas_graphql = config
+            .eas_graphql
+            .clone()
+            .unwrap_or_else(|| eas_graphql.to_string());
+        let attestations = fetch_attestations(&eas_graphql, &config)
+            .await
+            .with_context(|| format!("Failed to fetch the recipients of role {}", config.id))?;
+        let attestations = Arc::new(RwLock::new(attestations));
+        let a = attestations.clone();
+        let c = config.clone();
+        let poller = spawn(async move {
+            let mut itv = interval(Duration::from_secs(c.refresh_seconds));
+            loop {
+                itv.tick().await;
+                match fetch_attestations(&eas_graphql, &c).await {
+                    Ok(new_attestations) => {
+                        info!("Updating recipients of role {}", c.id);
+                        let mut attestations = a.write().await;
+                        *attestations = new_attestations;
+                    }
+                    Err(e) => {
+                        error!("Failed to fetch recipients of role {}: {}", c.id, e);
+                    }
+                }
+            }
+        });
+
+        Ok((
+            AttestationRole {
+                config,
+                attestations,
+            },
+            poller,
+        ))
+    }
+}
+
+impl RoleProvider for AttestationRole {
+    fn id(&self) -> &str {
+        &self.config.id
+    }
+
+    fn role(&self) -> u8 {
+        self.config.role
+    }
+
+    fn label(&self) -> &str {
+        &self.config.label
+    }
+
+    /// At a snapshot, the recipients as selected at its time from the
+    /// attestations made by then.
+    fn is_eligible(
+        &self,
+        address: Address,
+        snapshot: Option<Snapshot>,
+    ) -> BoxFuture<'_, Result<bool>> {
+        async move {
+            let a = self.attestations.read().await;
+            match snapshot {
+                Some(snapshot) => Ok(select_recipients(
+                    &self.config,
+                    &a.schema,
+                    &a.attestations,
+                    snapshot.timestamp,
+                )?
+                .contains(&address)),
+                None => Ok(a.recipients.contains(&address)),
+            }
+        }
+        .boxed()
+    }
+}
+
+/// Every attestation of the schema by the allowed attesters. Revoked ones
+/// are kept, as they still count at snapshots before their revocation.
+async fn fetch_attestations(
+    eas_graphql: &str,
+    config: &AttestationRoleConfig,
+) -> Result<Attestations> {
+    let mut filter = json!({});
+    if !config.attesters.is_empty() {
+        filter["attester"] = json!({ "in": config.attesters });
+    }
+
+    let client = Client::new();
+    let mut schema;
+    let mut attestations = vec![];
+    loop {
+        let response = client
+            .post(eas_graphql)
+            .json(&json!({
+                "query": r#"
+                    query Query($where: SchemaWhereUniqueInput!, $take: Int, $skip: Int, $attestationsWhere2: AttestationWhereInput, $orderBy: [AttestationOrderByWithRelationInput!]) {
+                      schema(where: $where) {
+                        schema
+                        attestations(take: $take, skip: $skip, where: $attestationsWhere2, orderBy: $orderBy) {
+                          data
+                          recipient
+                          timeCreated
+                          revoked
+                          revocationTime
+                          expirationTime
+                        }
+                      }
+                    }
+                "#,
+                "variables": {
+                  "where": { "id": config.schema },
+                  "take": PAGE_SIZE,
+                  "skip": attestations.len(),
+                  "attestationsWhere2": filter,
+                  "orderBy": [{ "timeCreated": "asc" }]
+                }
+            }))
+            .send()
+            .await?
+            .json::<Value>()
+            .await?;
+        if let Some(errors) = response.get("errors").filter(|e| !e.is_null()) {
+            bail!("EAS GraphQL error: {}", errors);
+        }
+        // the schema does not exist on every network, no attestations then
+        let page = &response["data"]["schema"];
+        if page.is_null() {
+            return Ok(Attestations::default());
+        }
+        schema = page["schema"].as_str().unwrap_or_default().to_string();
+        let page = from_value::<Vec<EasAttestation>>(page["attestations"].clone())?;
+        let full = page.len() == PAGE_SIZE;
+        attestations.extend(page);
+        if !full {
+            break;
+        }
+    }
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let recipients = select_recipients(config, &schema, &attestations, now)?;
+    Ok(Attestations {
+        schema,
+        attestations,
+        recipients,
     })
 }
+
+/// Recipients granted the role by `attestations` of `schema` at unix time
+/// `time`. Attestations whose data does not decode are ignored, and without
+/// any live attestation, as when the schema does not exist on the network,
+/// the predicate is not even decoded.
+pub fn select_recipients(
+    config: &AttestationRoleConfig,
+    schema: &str,
+    attestations: &[EasAttestation],
+    time: u64,
+) -> Result<HashSet<Address>> {
+    let made = attestations.iter().filter(|a| a.time_created <= time);
+    let live = made
+        .clone()
+        .filter(|a| a.expiration_time == 0 || a.expiration_time > time)
+        .filter(|a| config.revocation == RevocationPolicy::Include || !a.revoked_at(time));
+    if live.clone().next().is_none() {
+        return Ok(HashSet::new());
+    }
+
+    let selected = match &config.predicate {
+        None => live.collect::<Vec<_>>(),
+        Some(predicate) => {
+            let fields = parse_schema(schema)?;
+            let index = fields
+                .iter()
+                .position(|(name, _)| *name == predicate.field)
+                .ok_or_else(|| anyhow!("Schema has no field {}", predicate.field))?;
+            let ty = DynSolType::Tuple(fields.into_iter().map(|(_, ty)| ty).collect());
+            let values = live
+                .filter_map(|a| {
+                    let data = ty.abi_decode_params(&a.data).ok()?;
+                    Some((a, field_text(data.as_tuple()?.get(index)?)?))
+                })
+                .collect::<Vec<_>>();
+            let latest = values
+                .iter()
+                .map(|(_, value)| value.as_str())
+                .max_by(|a, b| compare(a, b));
+            values
+                .iter()
+                .filter(|(_, value)| matches(&predicate.condition, value, latest))
+                .map(|(a, _)| *a)
+                .collect()
+        }
+    };
+
+    let revoked = match config.revocation {
+        RevocationPolicy::Strict => made
+            .filter(|a| a.revoked_at(time))
+            .map(|a| a.recipient)
+            .collect(),
+        _ => HashSet::new(),
+    };
+    Ok(selected
+        .into_iter()
+        .map(|a| a.recipient)
+        .filter(|recipient| !revoked.contains(recipient))
+        .collect())
+}
+
+/// Names and types of the fields of an EAS schema, e.g.
+/// `string rpgfRound,address referredBy,string referredMethod`.
+pub fn parse_schema(schema: &str) -> Result<Vec<(String, DynSolType)>> {
+    if schema.trim().is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut fields = vec![];
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in schema.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                fields.push(&schema[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    fields.push(&schema[start..]);
+
+    fields
+        .into_iter()
+        .map(|field| {
+            let (ty, name) = field
+                .trim()
+                .rsplit_once(' ')
+                .ok_or_else(|| anyhow!("Schema field {:?} has no name", field))?;
+            let ty = DynSolType::parse(ty.trim())
+                .with_context(|| format!("Invalid type of schema field {}", name))?;
+            Ok((name.to_string(), ty))
+        })
+        .collect()
+}
+
+fn field_text(value: &DynSolValue) -> Option<String> {
+    match value {
+        DynSolValue::Bool(b) => Some(b.to_string()),
+        DynSolValue::Int(i, _) => Some(i.to_string()),
+        DynSolValue::Uint(u, _) => Some(u.to_string()),
+        DynSolValue::Address(a) => Some(a.to_string()),
+        DynSolValue::FixedBytes(word, size) => Some(hex::encode_prefixed(&word[..*size])),
+        DynSolValue::Bytes(b) => Some(hex::encode_prefixed(b)),
+        DynSolValue::String(s) => Some(s.clone()),
+        _ => None,
+    }
+}
+
+/// Compare as numbers when both values parse as one, as text otherwise.
+fn compare(a: &str, b: &str) -> Ordering {
+    match (a.parse::<U256>(), b.parse::<U256>()) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        _ => a.cmp(b),
+    }
+}
+
+fn matches(condition: &Condition, value: &str, latest: Option<&str>) -> bool {
+    match condition {
+        Condition::Eq { value: expected } => compare(value, expected).is_eq(),
+        Condition::Ne { value: expected } => compare(value, expected).is_ne(),
+        Condition::In { values } => values.iter().any(|v| compare(value, v).is_eq()),
+        Condition::Gte { value: bound } => compare(value, bound).is_ge(),
+        Condition::Lte { value: bound } => compare(value, bound).is_le(),
+        Condition::Latest => latest.is_some_and(|latest| compare(value, latest).is_eq()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::{
+        primitives::{address, b256},
+        sol_types::SolValue,
+    };
+
+    use super::*;
+    use crate::config::FieldPredicate;
+
+    const SCHEMA: &str = "string rpgfRound,address referredBy,string referredMethod";
+
+    fn attestation(recipient: Address, round: &str, time_created: u64) -> EasAttestation {
+        EasAttestation {
+            recipient,
+            data: (round.to_string(), Address::ZERO, "".to_string())
+                .abi_encode_params()
+                .into(),
+            time_created,
+            revoked: false,
+            revocation_time: 0,
+            expiration_time: 0,
+        }
+    }
+
+    fn badgeholder() -> AttestationRoleConfig {
+        AttestationRoleConfig {
+            id: "badgeholder".to_string(),
+            role: 1,
+            label: "Badgeholder".to_string(),
+            schema: b256!("fdcfdad2dbe7489e0ce56b260348b7f14e8365a8a325aef9834818c00d46b31b"),
+            attesters: vec![],
+            predicate: Some(FieldPredicate {
+                field: "rpgfRound".to_string(),
+                condition: Condition::Latest,
+            }),
+            revocation: RevocationPolicy::Exclude,
+            eas_graphql: None,
+            refresh_seconds: 60,
+        }
+    }
+
+    #[test]
+    fn latest_round_recipients() -> Result<()> {
+        let alice = address!("000000000000000000000000000000000000a11c");
+        let bob = address!("0000000000000000000000000000000000000b0b");
+        let carol = address!("00000000000000000000000000000000000ca201");
+        let mut config = badgeholder();
+        let attestations = [
+            attestation(alice, "9", 100),
+            attestation(bob, "10", 500),
+            EasAttestation {
+                revoked: true,
+                revocation_time: 800,
+                ..attestation(carol, "10", 500)
+            },
+            attestation(alice, "10", 500),
+            EasAttestation {
+                data: Bytes::from_static(b"garbage"),
+                ..attestation(carol, "11", 500)
+            },
+            EasAttestation {
+                expiration_time: 100,
+                ..attestation(carol, "11", 0)
+            },
+        ];
+
+        let select = |config: &AttestationRoleConfig| {
+            select_recipients(config, SCHEMA, &attestations, 1_000)
+        };
+        assert_eq!(select(&config)?, HashSet::from([alice, bob]));
+        let at = |time| select_recipients(&config, SCHEMA, &attestations, time);
+        assert_eq!(at(300)?, HashSet::from([alice]));
+        assert_eq!(at(600)?, HashSet::from([alice, bob, carol]));
+        config.revocation = RevocationPolicy::Include;
+        assert_eq!(select(&config)?, HashSet::from([alice, bob, carol]));
+        config.revocation = RevocationPolicy::Strict;
+        config.predicate = None;
+        assert_eq!(select(&config)?, HashSet::from([alice, bob]));
+        config.predicate = Some(FieldPredicate {
+            field: "rpgfRound".to_string(),
+            condition: Condition::Lte {
+                value: "9".to_string(),
+            },
+        });
+        assert_eq!(select(&config)?, HashSet::from([alice]));
+        config.predicate = Some(FieldPredicate {
+            field: "round".to_string(),
+            condition: Condition::Latest,
+        });
+        assert!(select(&config).is_err());
+
+        assert_eq!(parse_schema("")?, vec![]);
+        assert_eq!(
+            parse_schema("uint256 role, (uint8,string) pair")?,
+            vec![
+                ("role".to_string(), DynSolType::Uint(256)),
+                (
+                    "pair".to_string(),
+                    DynSolType::Tuple(vec![DynSolType::Uint(8), DynSolType::String])
+                ),
+            ]
+        );
+        assert!(parse_schema("uint256").is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn missing_schema_at_snapshot() -> Result<()> {
+        let alice = address!("000000000000000000000000000000000000a11c");
+        // what `fetch_attestations` returns where the schema does not exist
+        let role = AttestationRole {
+            config: badgeholder(),
+            attestations: Arc::new(RwLock::new(Attestations::default())),
+        };
+        let snapshot = Snapshot {
+            block: 1,
+            timestamp: 1_000,
+        };
+        assert!(!role.is_eligible(alice, Some(snapshot)).await?);
+        assert!(!role.is_eligible(alice, None).await?);
+
+        // before the first attestation of an existing schema
+        let attestations = [attestation(alice, "10", 500)];
+        assert!(select_recipients(&badgeholder(), "", &attestations, 300)?.is_empty());
+        Ok(())
+    }
+}
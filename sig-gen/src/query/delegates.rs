@@ -0,0 +1,341 @@
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+use alloy::{
+    primitives::{Address, U256},
+    providers::{Provider, ReqwestProvider},
+    rpc::types::Filter,
+    sol_types::SolEvent,
+};
+use anyhow::{bail, Result};
+use futures::{future::BoxFuture, FutureExt};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    select, spawn,
+    sync::RwLock,
+    task::JoinHandle,
+    time::{interval, Duration},
+};
+use tracing::{error, info};
+
+use super::{OptimismToken, RoleProvider, Snapshot};
+use crate::{
+    config::{DelegateIndexConfig, DelegateTier, DelegateTierConfig},
+    store::JsonLog,
+};
+
+/// Entry of the delegate index log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum IndexEntry {
+    Votes {
+        delegate: Address,
+        votes: U256,
+    },
+    /// Every event up to `block` is in the log.
+    Synced {
+        block: u64,
+    },
+}
+
+/// Votes of every delegate, from the `DelegateVotesChanged` events up to
+/// `next_block`.
+#[derive(Debug, Clone, Default)]
+pub struct DelegateVotes {
+    pub next_block: u64,
+    votes: HashMap<Address, U256>,
+    total: U256,
+    /// Votes of all delegates in decreasing order.
+    ranking: Vec<U256>,
+}
+
+impl DelegateVotes {
+    /// Rebuild the votes from the entries of the index log.
+    pub fn replay(entries: impl IntoIterator<Item = IndexEntry>) -> Self {
+        let mut votes = DelegateVotes::default();
+        for entry in entries {
+            match entry {
+                IndexEntry::Votes { delegate, votes: v } => votes.set(delegate, v),
+                IndexEntry::Synced { block } => votes.next_block = block + 1,
+            }
+        }
+        votes.rerank();
+        votes
+    }
+
+    /// Record the latest votes of `delegate`. The ranking is stale until
+    /// `rerank`.
+    pub fn set(&mut self, delegate: Address, votes: U256) {
+        let previous = if votes.is_zero() {
+            self.votes.remove(&delegate)
+        } else {
+            self.votes.insert(delegate, votes)
+        };
+        self.total = self.total - previous.unwrap_or_default() + votes;
+    }
+
+    /// Log entries holding the same votes, one per delegate and the last
+    /// block synced.
+    pub fn entries(&self) -> Vec<IndexEntry> {
+        self.votes
+            .iter()
+            .map(|(delegate, votes)| IndexEntry::Votes {
+                delegate: *delegate,
+                votes: *votes,
+            })
+            .chain(
+                self.next_block
+                    .checked_sub(1)
+                    .map(|block| IndexEntry::Synced { block }),
+            )
+            .collect()
+    }
+
+    pub fn rerank(&mut self) {
+        self.ranking = self.votes.values().copied().collect();
+        self.ranking.sort_unstable_by(|a, b| b.cmp(a));
+    }
+
+    pub fn votes(&self, delegate: Address) -> U256 {
+        self.votes.get(&delegate).copied().unwrap_or_default()
+    }
+
+    /// The votable supply.
+    pub fn total(&self) -> U256 {
+        self.total
+    }
+
+    /// 1 plus the number of delegates with more votes, `None` without votes.
+    pub fn rank(&self, delegate: Address) -> Option<usize> {
+        let votes = self.votes.get(&delegate)?;
+        Some(self.ranking.partition_point(|v| v > votes) + 1)
+    }
+
+    pub fn admits(&self, tier: &DelegateTier, delegate: Address) -> bool {
+        let votes = self.votes(delegate);
+        if votes.is_zero() {
+            return false;
+        }
+        match *tier {
+            DelegateTier::MinVotes { min_votes } => votes >= min_votes,
+            DelegateTier::MinShare { min_bps } => {
+                votes * U256::from(10_000) >= self.total * U256::from(min_bps)
+            }
+            DelegateTier::Top { top } => self.rank(delegate).is_some_and(|rank| rank <= top),
+        }
+    }
+}
+
+/// Votes of the delegates of a token, kept up to date by a poller and
+/// persisted in a JSON lines log.
+#[derive(Debug, Clone)]
+pub struct DelegateIndex {
+    pub votes: Arc<RwLock<DelegateVotes>>,
+}
+
+impl DelegateIndex {
+    /// Replay the log at `path`, catch up with the `DelegateVotesChanged`
+    /// events of `token` and scan new blocks every `poll_seconds`, up to
+    /// `confirmations` under the latest. Every hour the log is rewritten as
+    /// the current votes.
+    pub async fn new(
+        provider: ReqwestProvider,
+        token: Address,
+        config: DelegateIndexConfig,
+        path: impl AsRef<Path>,
+    ) -> Result<(Self, JoinHandle<()>)> {
+        let (log, entries) = JsonLog::<IndexEntry>::open(path).await?;
+        let mut votes = DelegateVotes::replay(entries);
+        votes.next_block = votes.next_block.max(config.from_block);
+        let index = DelegateIndex {
+            votes: Arc::new(RwLock::new(votes)),
+        };
+
+        index.sync(&provider, token, &config, &log).await?;
+        {
+            let votes = index.votes.read().await;
+            info!(
+                "Indexed {} delegates of {}, up to block {}",
+                votes.votes.len(),
+                token,
+                votes.next_block - 1
+            );
+        }
+
+        let i = index.clone();
+        let poller = spawn(async move {
+            let mut itv = interval(Duration::from_secs(config.poll_seconds));
+            let mut compaction = interval(Duration::from_secs(3600));
+            loop {
+                select! {
+                    _ = itv.tick() => {
+                        if let Err(e) = i.sync(&provider, token, &config, &log).await {
+                            error!("Failed to update the delegates of {}: {}", token, e);
+                        }
+                    }
+                    _ = compaction.tick() => {
+                        if let Err(e) = i.compact(&log).await {
+                            error!("Failed to compact the delegate log of {}: {}", token, e);
+                        }
+                    }
+                }
+            }
+        });
+
+        Ok((index, poller))
+    }
+
+    /// Replace the log with the entries of the current votes, dropping the
+    /// superseded ones and all but the last `Synced`.
+    async fn compact(&self, log: &JsonLog<IndexEntry>) -> Result<()> {
+        let entries = self.votes.read().await.entries();
+        log.rewrite(&entries).await
+    }
+
+    /// Apply the events up to the latest confirmed block, `chunk_blocks` at a
+    /// time.
+    async fn sync(
+        &self,
+        provider: &ReqwestProvider,
+        token: Address,
+        config: &DelegateIndexConfig,
+        log: &JsonLog<IndexEntry>,
+    ) -> Result<()> {
+        let latest = provider
+            .get_block_number()
+            .await?
+            .saturating_sub(config.confirmations);
+        loop {
+            let from = self.votes.read().await.next_block;
+            if from > latest {
+                return Ok(());
+            }
+            let to = latest.min(from + config.chunk_blocks - 1);
+
+            let filter = Filter::new()
+                .address(token)
+                .event_signature(OptimismToken::DelegateVotesChanged::SIGNATURE_HASH)
+                .from_block(from)
+                .to_block(to);
+            let mut changes = vec![];
+            for l in provider.get_logs(&filter).await? {
+                let event = OptimismToken::DelegateVotesChanged::decode_log(&l.inner, true)?;
+                let entry = IndexEntry::Votes {
+                    delegate: event.delegate,
+                    votes: event.newBalance,
+                };
+                log.append(&entry).await?;
+                changes.push(entry);
+            }
+            log.append(&IndexEntry::Synced { block: to }).await?;
+
+            let mut votes = self.votes.write().await;
+            for change in &changes {
+                if let IndexEntry::Votes { delegate, votes: v } = *change {
+                    votes.set(delegate, v);
+                }
+            }
+            if !changes.is_empty() {
+                votes.rerank();
+            }
+            votes.next_block = to + 1;
+        }
+    }
+}
+
+/// Delegates in a tier of the delegate index, at its latest block only.
+#[derive(Debug, Clone)]
+pub struct DelegateTierRole {
+    pub config: DelegateTierConfig,
+    pub index: DelegateIndex,
+}
+
+impl RoleProvider for DelegateTierRole {
+    fn id(&self) -> &str {
+        &self.config.id
+    }
+
+    fn role(&self) -> u8 {
+        self.config.role
+    }
+
+    fn label(&self) -> &str {
+        &self.config.label
+    }
+
+    fn is_eligible(
+        &self,
+        address: Address,
+        snapshot: Option<Snapshot>,
+    ) -> BoxFuture<'_, Result<bool>> {
+        async move {
+            if snapshot.is_some() {
+                bail!("Role {} only holds the latest votes", self.config.id);
+            }
+            let votes = self.index.votes.read().await;
+            Ok(votes.admits(&self.config.tier, address))
+        }
+        .boxed()
+    }
+
+    fn supports_snapshots(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::address;
+
+    use super::*;
+
+    #[test]
+    fn delegate_tiers() {
+        let alice = address!("000000000000000000000000000000000000a11c");
+        let bob = address!("0000000000000000000000000000000000000b0b");
+        let carol = address!("00000000000000000000000000000000000ca201");
+        let dave = address!("00000000000000000000000000000000000da4e0");
+        let entry = |delegate, votes: u64| IndexEntry::Votes {
+            delegate,
+            votes: U256::from(votes),
+        };
+        let votes = DelegateVotes::replay([
+            entry(alice, 100),
+            entry(bob, 50),
+            entry(carol, 10),
+            IndexEntry::Synced { block: 41 },
+            entry(carol, 50),
+            entry(dave, 5),
+            entry(dave, 0),
+        ]);
+
+        assert_eq!(votes.next_block, 42);
+        assert_eq!(votes.total(), U256::from(200));
+        assert_eq!(votes.rank(alice), Some(1));
+        assert_eq!(votes.rank(bob), Some(2));
+        assert_eq!(votes.rank(carol), Some(2));
+        assert_eq!(votes.rank(dave), None);
+
+        let top = |top| DelegateTier::Top { top };
+        assert!(votes.admits(&top(2), carol));
+        assert!(!votes.admits(&top(1), bob));
+        assert!(!votes.admits(&top(10), dave));
+        let share = |min_bps| DelegateTier::MinShare { min_bps };
+        assert!(votes.admits(&share(2_500), bob));
+        assert!(!votes.admits(&share(2_501), bob));
+        let min = |min_votes: u64| DelegateTier::MinVotes {
+            min_votes: U256::from(min_votes),
+        };
+        assert!(votes.admits(&min(100), alice));
+        assert!(!votes.admits(&min(51), carol));
+
+        let entries = votes.entries();
+        assert_eq!(entries.len(), 4);
+        let compacted = DelegateVotes::replay(entries);
+        assert_eq!(compacted.next_block, 42);
+        assert_eq!(compacted.total(), votes.total());
+        for delegate in [alice, bob, carol, dave] {
+            assert_eq!(compacted.votes(delegate), votes.votes(delegate));
+            assert_eq!(compacted.rank(delegate), votes.rank(delegate));
+        }
+    }
+}
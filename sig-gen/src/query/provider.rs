@@ -0,0 +1,215 @@
+use std::{fmt::Debug, sync::Arc};
+
+use alloy::primitives::Address;
+use anyhow::{bail, Result};
+use futures::future::{join_all, BoxFuture};
+use tracing::warn;
+
+use super::{Snapshot, SNAPSHOT_ROLE};
+
+/// Source of a role: who holds it and how it is signed.
+pub trait RoleProvider: Debug + Send + Sync {
+    /// Stable identifier of the role, e.g. `badgeholder`.
+    fn id(&self) -> &str;
+
+    /// Role number signed in the identity and attested by the circuit.
+    fn role(&self) -> u8;
+
+    /// Name of the role in responses, the `role_str`.
+    fn label(&self) -> &str;
+
+    /// Whether `address` holds the role, at `snapshot` when given.
+    fn is_eligible(
+        &self,
+        address: Address,
+        snapshot: Option<Snapshot>,
+    ) -> BoxFuture<'_, Result<bool>>;
+
+    /// Whether the role can be checked at a past snapshot. Roles that cannot
+    /// are left out of snapshot requests.
+    fn supports_snapshots(&self) -> bool {
+        true
+    }
+}
+
+/// Roles of an address, and the roles that could not be checked.
+#[derive(Debug, Clone, Default)]
+pub struct Eligibility {
+    pub roles: Vec<Arc<dyn RoleProvider>>,
+    pub unavailable: Vec<Arc<dyn RoleProvider>>,
+}
+
+/// Role providers of a network, in role number order.
+#[derive(Debug, Clone, Default)]
+pub struct RoleRegistry {
+    providers: Vec<Arc<dyn RoleProvider>>,
+}
+
+impl RoleRegistry {
+    /// Add `provider`, whose id and role number must not be taken yet. Role
+    /// numbers with the `SNAPSHOT_ROLE` bit are kept for snapshots.
+    pub fn register(&mut self, provider: impl RoleProvider + 'static) -> Result<()> {
+        if provider.role() & SNAPSHOT_ROLE != 0 {
+            bail!(
+                "Role {} ({}) is above {}, the roles held at a snapshot",
+                provider.id(),
+                provider.role(),
+                SNAPSHOT_ROLE - 1
+            );
+        }
+        if let Some(taken) = self
+            .providers
+            .iter()
+            .find(|p| p.id() == provider.id() || p.role() == provider.role())
+        {
+            bail!(
+                "Role {} ({}) conflicts with role {} ({})",
+                provider.id(),
+                provider.role(),
+                taken.id(),
+                taken.role()
+            );
+        }
+
+        let i = self
+            .providers
+            .partition_point(|p| p.role() < provider.role());
+        self.providers.insert(i, Arc::new(provider));
+        Ok(())
+    }
+
+    pub fn providers(&self) -> &[Arc<dyn RoleProvider>] {
+        &self.providers
+    }
+
+    pub fn get(&self, role: u8) -> Option<&Arc<dyn RoleProvider>> {
+        self.providers.iter().find(|p| p.role() == role)
+    }
+
+    /// Providers of the roles `address` holds, at `snapshot` when given,
+    /// checked concurrently. A failing provider is logged and reported as
+    /// unavailable without failing the others.
+    pub async fn eligible(&self, address: Address, snapshot: Option<Snapshot>) -> Eligibility {
+        let providers = self
+            .providers
+            .iter()
+            .filter(|p| snapshot.is_none() || p.supports_snapshots())
+            .collect::<Vec<_>>();
+        let results = join_all(providers.iter().map(|p| p.is_eligible(address, snapshot))).await;
+
+        let mut eligibility = Eligibility::default();
+        for (provider, result) in providers.into_iter().zip(results) {
+            match result {
+                Ok(true) => eligibility.roles.push(provider.clone()),
+                Ok(false) => {}
+                Err(e) => {
+                    warn!(
+                        "Failed to check role {} of {}: {}",
+                        provider.id(),
+                        address,
+                        e
+                    );
+                    eligibility.unavailable.push(provider.clone());
+                }
+            }
+        }
+        eligibility
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::address;
+    use futures::FutureExt;
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct Fixed(&'static str, u8, Address);
+
+    #[derive(Debug)]
+    struct Failing;
+
+    impl RoleProvider for Failing {
+        fn id(&self) -> &str {
+            "failing"
+        }
+
+        fn role(&self) -> u8 {
+            5
+        }
+
+        fn label(&self) -> &str {
+            "failing"
+        }
+
+        fn is_eligible(&self, _: Address, _: Option<Snapshot>) -> BoxFuture<'_, Result<bool>> {
+            async move { bail!("RPC unreachable") }.boxed()
+        }
+    }
+
+    impl RoleProvider for Fixed {
+        fn id(&self) -> &str {
+            self.0
+        }
+
+        fn role(&self) -> u8 {
+            self.1
+        }
+
+        fn label(&self) -> &str {
+            self.0
+        }
+
+        fn is_eligible(
+            &self,
+            address: Address,
+            _: Option<Snapshot>,
+        ) -> BoxFuture<'_, Result<bool>> {
+            let eligible = address == self.2;
+            async move { Ok(eligible) }.boxed()
+        }
+
+        fn supports_snapshots(&self) -> bool {
+            self.0 != "c"
+        }
+    }
+
+    #[tokio::test]
+    async fn registry_order_and_conflicts() -> Result<()> {
+        let alice = address!("000000000000000000000000000000000000a11c");
+        let bob = address!("0000000000000000000000000000000000000b0b");
+        let mut registry = RoleRegistry::default();
+        registry.register(Fixed("b", 2, alice))?;
+        registry.register(Fixed("a", 1, bob))?;
+        registry.register(Fixed("c", 3, alice))?;
+        assert!(registry.register(Fixed("b", 4, alice)).is_err());
+        assert!(registry.register(Fixed("d", 1, alice)).is_err());
+        assert!(registry.register(Fixed("e", SNAPSHOT_ROLE, alice)).is_err());
+
+        let ids = |providers: &[Arc<dyn RoleProvider>]| {
+            providers
+                .iter()
+                .map(|p| p.id().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(registry.providers()), ["a", "b", "c"]);
+        assert_eq!(ids(&registry.eligible(alice, None).await.roles), ["b", "c"]);
+        let snapshot = Snapshot {
+            block: 1,
+            timestamp: 2,
+        };
+        assert_eq!(
+            ids(&registry.eligible(alice, Some(snapshot)).await.roles),
+            ["b"]
+        );
+
+        registry.register(Failing)?;
+        let eligibility = registry.eligible(alice, None).await;
+        assert_eq!(ids(&eligibility.roles), ["b", "c"]);
+        assert_eq!(ids(&eligibility.unavailable), ["failing"]);
+        assert_eq!(registry.get(1).map(|p| p.id()), Some("a"));
+        assert!(registry.get(4).is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,135 @@
+use alloy::{
+    primitives::{Address, Uint, U256},
+    providers::ReqwestProvider,
+    rpc::types::BlockId,
+};
+use anyhow::Result;
+use futures::{future::BoxFuture, FutureExt};
+use tokio::{task::JoinHandle, try_join};
+
+use super::{AttestationRole, OptimismToken, RoleProvider, RoleRegistry, Snapshot};
+use crate::config::AttestationRoleConfig;
+
+/// Role 0, held by everyone, for attestations that disclose no role.
+#[derive(Debug, Clone, Copy)]
+pub struct HiddenRole;
+
+impl RoleProvider for HiddenRole {
+    fn id(&self) -> &str {
+        "hidden"
+    }
+
+    fn role(&self) -> u8 {
+        0
+    }
+
+    fn label(&self) -> &str {
+        "Hidden"
+    }
+
+    fn is_eligible(&self, _: Address, _: Option<Snapshot>) -> BoxFuture<'_, Result<bool>> {
+        async { Ok(true) }.boxed()
+    }
+}
+
+/// Addresses with votes of the OP token delegated to them.
+#[derive(Debug, Clone)]
+pub struct DelegateRole {
+    pub op_token: Address,
+    pub provider: ReqwestProvider,
+}
+
+impl RoleProvider for DelegateRole {
+    fn id(&self) -> &str {
+        "delegate"
+    }
+
+    fn role(&self) -> u8 {
+        2
+    }
+
+    fn label(&self) -> &str {
+        "Delegate"
+    }
+
+    fn is_eligible(
+        &self,
+        address: Address,
+        snapshot: Option<Snapshot>,
+    ) -> BoxFuture<'_, Result<bool>> {
+        async move {
+            let contract = OptimismToken::new(self.op_token, &self.provider);
+            let votes = match snapshot {
+                Some(snapshot) => {
+                    contract
+                        .getPastVotes(address, U256::from(snapshot.block))
+                        .call()
+                        .await?
+                        ._0
+                }
+                None => contract.getVotes(address).call().await?._0,
+            };
+            Ok(votes > Uint::ZERO)
+        }
+        .boxed()
+    }
+}
+
+/// Holders of the OP token who delegated their votes.
+#[derive(Debug, Clone)]
+pub struct DelegatorRole {
+    pub op_token: Address,
+    pub provider: ReqwestProvider,
+}
+
+impl RoleProvider for DelegatorRole {
+    fn id(&self) -> &str {
+        "delegator"
+    }
+
+    fn role(&self) -> u8 {
+        3
+    }
+
+    fn label(&self) -> &str {
+        "Delegator"
+    }
+
+    fn is_eligible(
+        &self,
+        address: Address,
+        snapshot: Option<Snapshot>,
+    ) -> BoxFuture<'_, Result<bool>> {
+        async move {
+            let contract = OptimismToken::new(self.op_token, &self.provider);
+            let block = snapshot.map_or(BlockId::latest(), |s| BlockId::number(s.block));
+            let d = || async { contract.delegates(address).block(block).call().await };
+            let b = || async { contract.balanceOf(address).block(block).call().await };
+            let (delegate, balance) = try_join!(d(), b())?;
+            Ok(delegate._0 != Address::ZERO && balance._0 > Uint::ZERO)
+        }
+        .boxed()
+    }
+}
+
+impl RoleRegistry {
+    /// Registry of the hidden, badgeholder, delegate and delegator roles, with
+    /// the poller of the badgeholders.
+    pub async fn optimism(
+        provider: ReqwestProvider,
+        op_token: Address,
+        eas_graphql: &str,
+    ) -> Result<(Self, JoinHandle<()>)> {
+        let (badgeholders, poller) =
+            AttestationRole::new(AttestationRoleConfig::badgeholder(), eas_graphql).await?;
+        let mut registry = RoleRegistry::default();
+        registry.register(HiddenRole)?;
+        registry.register(badgeholders)?;
+        registry.register(DelegateRole {
+            op_token,
+            provider: provider.clone(),
+        })?;
+        registry.register(DelegatorRole { op_token, provider })?;
+        Ok((registry, poller))
+    }
+}
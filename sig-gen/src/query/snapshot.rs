@@ -0,0 +1,79 @@
+use alloy::{
+    providers::{Provider, ReqwestProvider},
+    rpc::types::{BlockId, BlockTransactionsKind},
+};
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+
+/// Past point of a chain requested for the role checks, e.g. the snapshot
+/// of a governance proposal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SnapshotRef {
+    Block(u64),
+    /// The last block at or before the timestamp.
+    Timestamp(u64),
+}
+
+/// Bit of the role numbers signed for a snapshot. A role held at a past
+/// block then never passes for one held now, and its timestamp for an epoch.
+pub const SNAPSHOT_ROLE: u8 = 0x80;
+
+/// Block roles are checked at, and its timestamp, which is signed in place
+/// of the epoch along with the `SNAPSHOT_ROLE` bit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Snapshot {
+    pub block: u64,
+    pub timestamp: u64,
+}
+
+impl Snapshot {
+    /// Role number signed for `role` held at the snapshot.
+    pub fn role(role: u8) -> u8 {
+        role | SNAPSHOT_ROLE
+    }
+
+    /// Resolve `reference` on the chain of `provider`. The block must be
+    /// before the latest one, as `getPastVotes` requires.
+    pub async fn resolve(provider: &ReqwestProvider, reference: SnapshotRef) -> Result<Self> {
+        let latest = provider.get_block_number().await?;
+        let block = match reference {
+            SnapshotRef::Block(block) => block,
+            SnapshotRef::Timestamp(timestamp) => block_at(provider, timestamp, latest).await?,
+        };
+        if block >= latest {
+            bail!("Snapshot block {} is not before block {}", block, latest);
+        }
+
+        Ok(Snapshot {
+            block,
+            timestamp: block_timestamp(provider, block).await?,
+        })
+    }
+}
+
+/// Last block at or before `timestamp`, by binary search up to `latest`.
+pub async fn block_at(provider: &ReqwestProvider, timestamp: u64, latest: u64) -> Result<u64> {
+    if block_timestamp(provider, 0).await? > timestamp {
+        bail!("Timestamp {} is before the first block", timestamp);
+    }
+
+    let (mut low, mut high) = (0, latest);
+    while low < high {
+        let mid = low + (high - low + 1) / 2;
+        if block_timestamp(provider, mid).await? <= timestamp {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+    Ok(low)
+}
+
+async fn block_timestamp(provider: &ReqwestProvider, block: u64) -> Result<u64> {
+    provider
+        .get_block(BlockId::number(block), BlockTransactionsKind::Hashes)
+        .await?
+        .map(|block| block.header.timestamp)
+        .ok_or_else(|| anyhow!("Block {} not found", block))
+}
@@ -0,0 +1,127 @@
+use std::{collections::HashMap, sync::Arc};
+
+use alloy::{
+    primitives::Address,
+    providers::{Provider, ReqwestProvider},
+    rpc::types::BlockId,
+};
+use anyhow::Result;
+use futures::{future::BoxFuture, FutureExt};
+use tokio::sync::Mutex;
+
+use super::{block_at, RoleProvider, Snapshot, ERC1155, ERC20, ERC721};
+use crate::{
+    config::{TokenRoleConfig, TokenStandard},
+    contracts::revert_data,
+};
+
+/// Most snapshot timestamps whose block on the token's chain is remembered.
+const BLOCK_CACHE_SIZE: usize = 1024;
+
+/// Holders of at least `min_balance` of a token, as declared in the config.
+#[derive(Debug, Clone)]
+pub struct TokenRole {
+    pub config: TokenRoleConfig,
+    /// Chain of the network the role is signed for.
+    pub chain_id: u64,
+    /// Provider of the token's chain.
+    pub provider: ReqwestProvider,
+    /// Block of the token's chain at each snapshot timestamp, when it is
+    /// another chain.
+    blocks: Arc<Mutex<HashMap<u64, u64>>>,
+}
+
+impl TokenRole {
+    pub fn new(config: TokenRoleConfig, chain_id: u64, provider: ReqwestProvider) -> Self {
+        TokenRole {
+            config,
+            chain_id,
+            provider,
+            blocks: Arc::default(),
+        }
+    }
+
+    /// Block of the token's chain to read the snapshot at.
+    async fn block(&self, snapshot: Snapshot) -> Result<u64> {
+        if self.config.chain_id.unwrap_or(self.chain_id) == self.chain_id {
+            return Ok(snapshot.block);
+        }
+        // another chain, read at the same time
+        if let Some(block) = self.blocks.lock().await.get(&snapshot.timestamp) {
+            return Ok(*block);
+        }
+        let latest = self.provider.get_block_number().await?;
+        let block = block_at(&self.provider, snapshot.timestamp, latest).await?;
+        // the latest block may still be followed by one at the same time
+        if block < latest {
+            let mut blocks = self.blocks.lock().await;
+            if blocks.len() >= BLOCK_CACHE_SIZE {
+                blocks.clear();
+            }
+            blocks.insert(snapshot.timestamp, block);
+        }
+        Ok(block)
+    }
+}
+
+impl RoleProvider for TokenRole {
+    fn id(&self) -> &str {
+        &self.config.id
+    }
+
+    fn role(&self) -> u8 {
+        self.config.role
+    }
+
+    fn label(&self) -> &str {
+        &self.config.label
+    }
+
+    fn is_eligible(
+        &self,
+        address: Address,
+        snapshot: Option<Snapshot>,
+    ) -> BoxFuture<'_, Result<bool>> {
+        async move {
+            let config = &self.config;
+            let block = match snapshot {
+                None => BlockId::latest(),
+                Some(snapshot) => BlockId::number(self.block(snapshot).await?),
+            };
+            let balance = match (config.standard, config.token_id) {
+                (TokenStandard::Erc20, _) => {
+                    let token = ERC20::new(config.contract, &self.provider);
+                    token.balanceOf(address).block(block).call().await?._0
+                }
+                (TokenStandard::Erc721, None) => {
+                    let token = ERC721::new(config.contract, &self.provider);
+                    token.balanceOf(address).block(block).call().await?._0
+                }
+                (TokenStandard::Erc721, Some(token_id)) => {
+                    let token = ERC721::new(config.contract, &self.provider);
+                    return match token.ownerOf(token_id).block(block).call().await {
+                        Ok(owner) => Ok(owner._0 == address),
+                        // the token does not exist (yet, or any more)
+                        Err(alloy::contract::Error::TransportError(e))
+                            if revert_data(&e).is_some() =>
+                        {
+                            Ok(false)
+                        }
+                        Err(e) => Err(e.into()),
+                    };
+                }
+                (TokenStandard::Erc1155, token_id) => {
+                    let token = ERC1155::new(config.contract, &self.provider);
+                    token
+                        .balanceOf(address, token_id.unwrap_or_default())
+                        .block(block)
+                        .call()
+                        .await?
+                        ._0
+                }
+            };
+            Ok(balance >= config.min_balance)
+        }
+        .boxed()
+    }
+}
@@ -1,32 +1,36 @@
 use alloy::sol;
-use serde::{Deserialize, Serialize};
 
 sol! {
     #[sol(rpc)]
     contract OptimismToken {
+        event DelegateVotesChanged(address indexed delegate, uint256 previousBalance, uint256 newBalance);
+
         function getVotes(address account) external view returns (uint256);
+        function getPastVotes(address account, uint256 blockNumber) external view returns (uint256);
         function delegates(address account) external view returns (address);
         function balanceOf(address account) external view returns (uint256);
     }
-    struct Schema {
-        string rpgfRound;
-        address referredBy;
-        string referredMethod;
+}
+
+// One block per standard, as `sol!` refuses the same `balanceOf` twice in one.
+sol! {
+    #[sol(rpc)]
+    contract ERC20 {
+        function balanceOf(address account) external view returns (uint256);
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
-#[repr(u8)]
-pub enum Role {
-    Hidden,
-    Badgeholder,
-    Delegate,
-    Delegator,
+sol! {
+    #[sol(rpc)]
+    contract ERC721 {
+        function balanceOf(address owner) external view returns (uint256);
+        function ownerOf(uint256 tokenId) external view returns (address);
+    }
 }
 
-pub const ALL_ROLES: [Role; 4] = [
-    Role::Hidden,
-    Role::Badgeholder,
-    Role::Delegate,
-    Role::Delegator,
-];
+sol! {
+    #[sol(rpc)]
+    contract ERC1155 {
+        function balanceOf(address account, uint256 id) external view returns (uint256);
+    }
+}
@@ -0,0 +1,126 @@
+use std::fmt::{self, Display};
+
+use alloy::primitives::{FixedBytes, B256};
+use axum::Json;
+use hyper::StatusCode;
+use serde_json::{json, Value};
+
+/// Reasons for the relayer to refuse a transaction, each with its own code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RelayError {
+    MalformedCalldata(String),
+    UnsupportedSelector(FixedBytes<4>),
+    SchemaNotAllowed(B256),
+    NonceUsed(B256),
+    InvalidProof,
+    InvalidVerifier,
+    /// Any other named error of the AnonymousAttester.
+    Reverted(&'static str),
+    SimulationFailed(String),
+    Rpc(String),
+    /// The bundler refused the user operation, or could not be reached.
+    UserOpFailed(String),
+    GasLimitExceeded {
+        gas_limit: u128,
+        cap: u128,
+    },
+    /// The daily spending budget of the relayer is used up.
+    BudgetExhausted,
+    /// The relayer balance is under its minimum, or unknown.
+    BalanceExhausted,
+    /// Every funded wallet of the pool is failing.
+    NoHealthyWallet,
+    ProofOfWorkRequired {
+        difficulty: u32,
+    },
+    InsufficientWork {
+        difficulty: u32,
+    },
+    StaleProofOfWork,
+    ReusedProofOfWork,
+}
+
+impl RelayError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            RelayError::MalformedCalldata(_) => "malformed_calldata",
+            RelayError::UnsupportedSelector(_) => "unsupported_selector",
+            RelayError::SchemaNotAllowed(_) => "schema_not_allowed",
+            RelayError::NonceUsed(_) => "nonce_used",
+            RelayError::InvalidProof => "invalid_proof",
+            RelayError::InvalidVerifier => "invalid_verifier",
+            RelayError::Reverted(_) => "reverted",
+            RelayError::SimulationFailed(_) => "simulation_failed",
+            RelayError::Rpc(_) => "rpc_error",
+            RelayError::UserOpFailed(_) => "user_op_failed",
+            RelayError::GasLimitExceeded { .. } => "gas_limit_exceeded",
+            RelayError::BudgetExhausted => "budget_exhausted",
+            RelayError::BalanceExhausted => "balance_exhausted",
+            RelayError::NoHealthyWallet => "no_healthy_wallet",
+            RelayError::ProofOfWorkRequired { .. } => "pow_required",
+            RelayError::InsufficientWork { .. } => "insufficient_work",
+            RelayError::StaleProofOfWork => "stale_pow",
+            RelayError::ReusedProofOfWork => "reused_pow",
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            RelayError::InvalidVerifier | RelayError::Rpc(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            RelayError::NonceUsed(_) => StatusCode::CONFLICT,
+            RelayError::UserOpFailed(_) => StatusCode::BAD_GATEWAY,
+            RelayError::ProofOfWorkRequired { .. } => StatusCode::PRECONDITION_REQUIRED,
+            RelayError::InsufficientWork { .. }
+            | RelayError::StaleProofOfWork
+            | RelayError::ReusedProofOfWork => StatusCode::FORBIDDEN,
+            RelayError::BudgetExhausted
+            | RelayError::BalanceExhausted
+            | RelayError::NoHealthyWallet => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+impl Display for RelayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RelayError::MalformedCalldata(e) => write!(f, "Malformed calldata: {}", e),
+            RelayError::UnsupportedSelector(selector) => {
+                write!(f, "Unsupported function selector {}", selector)
+            }
+            RelayError::SchemaNotAllowed(schema) => write!(f, "Schema {} is not allowed", schema),
+            RelayError::NonceUsed(nonce) => write!(f, "Nonce {} is already used", nonce),
+            RelayError::InvalidProof => write!(f, "Invalid proof"),
+            RelayError::InvalidVerifier => write!(f, "Verifier is not set"),
+            RelayError::Reverted(error) => write!(f, "Transaction would revert with {}", error),
+            RelayError::SimulationFailed(e) => write!(f, "Simulation failed: {}", e),
+            RelayError::Rpc(e) => write!(f, "RPC error: {}", e),
+            RelayError::UserOpFailed(e) => write!(f, "Failed to send user operation: {}", e),
+            RelayError::GasLimitExceeded { gas_limit, cap } => {
+                write!(f, "Gas limit {} exceeds the cap of {}", gas_limit, cap)
+            }
+            RelayError::BudgetExhausted => write!(f, "Daily relayer budget exhausted"),
+            RelayError::BalanceExhausted => write!(f, "Relayer balance too low"),
+            RelayError::NoHealthyWallet => write!(f, "No relayer wallet is available"),
+            RelayError::ProofOfWorkRequired { difficulty } => {
+                write!(f, "Proof of work of difficulty {} required", difficulty)
+            }
+            RelayError::InsufficientWork { difficulty } => {
+                write!(f, "Proof of work below difficulty {}", difficulty)
+            }
+            RelayError::StaleProofOfWork => write!(f, "Proof of work timestamp out of range"),
+            RelayError::ReusedProofOfWork => write!(f, "Proof of work already used"),
+        }
+    }
+}
+
+impl std::error::Error for RelayError {}
+
+impl From<RelayError> for (StatusCode, Json<Value>) {
+    fn from(e: RelayError) -> Self {
+        (
+            e.status(),
+            Json(json!({ "error": e.code(), "message": e.to_string() })),
+        )
+    }
+}
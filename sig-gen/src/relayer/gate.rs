@@ -0,0 +1,181 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use alloy::primitives::{keccak256, Bytes, B256};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use super::RelayError;
+use crate::config::GateConfig;
+
+/// Domain of relay proofs of work, so no other hashcash stamp is accepted.
+pub const POW_DOMAIN: &[u8] = b"CURIA_RELAY_POW";
+
+const LOAD_WINDOW: u64 = 60;
+
+/// Hashcash stamp for one relayed call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofOfWork {
+    pub timestamp: u64,
+    pub nonce: u64,
+}
+
+impl ProofOfWork {
+    /// `keccak256(POW_DOMAIN ‖ chain_id ‖ timestamp ‖ nonce ‖ keccak256(input))`,
+    /// integers as 8 big-endian bytes.
+    pub fn digest(&self, chain_id: u64, input: &Bytes) -> B256 {
+        let mut preimage = POW_DOMAIN.to_vec();
+        preimage.extend_from_slice(&chain_id.to_be_bytes());
+        preimage.extend_from_slice(&self.timestamp.to_be_bytes());
+        preimage.extend_from_slice(&self.nonce.to_be_bytes());
+        preimage.extend_from_slice(keccak256(input).as_slice());
+        keccak256(preimage)
+    }
+
+    /// Find a stamp with at least `difficulty` leading zero bits.
+    pub fn solve(chain_id: u64, input: &Bytes, timestamp: u64, difficulty: u32) -> Self {
+        (0..)
+            .map(|nonce| ProofOfWork { timestamp, nonce })
+            .find(|pow| leading_zeros(&pow.digest(chain_id, input)) >= difficulty)
+            .unwrap()
+    }
+}
+
+fn leading_zeros(digest: &B256) -> u32 {
+    let mut zeros = 0;
+    for byte in digest.iter() {
+        zeros += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    zeros
+}
+
+/// Spam protection of the relayer, checked before any RPC call. A call
+/// carries a proof of work over its calldata, whose difficulty rises with the
+/// number of calls accepted in the last minute.
+#[derive(Debug, Clone)]
+pub struct Gate {
+    pub config: GateConfig,
+    accepted: Arc<Mutex<VecDeque<u64>>>,
+    /// Digests of accepted stamps, until they expire.
+    stamps: Arc<Mutex<HashMap<B256, u64>>>,
+}
+
+impl Gate {
+    pub fn new(config: GateConfig) -> Self {
+        Gate {
+            config,
+            accepted: Arc::new(Mutex::new(VecDeque::new())),
+            stamps: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Difficulty currently required of a proof of work.
+    pub async fn difficulty(&self) -> u32 {
+        let mut accepted = self.accepted.lock().await;
+        self.difficulty_at(&mut accepted, now())
+    }
+
+    /// One extra bit for every doubling of the load over the target rate.
+    fn difficulty_at(&self, accepted: &mut VecDeque<u64>, now: u64) -> u32 {
+        while accepted.front().is_some_and(|at| *at + LOAD_WINDOW <= now) {
+            accepted.pop_front();
+        }
+        let load = accepted.len() as u64 / self.config.target_per_minute.max(1);
+        (self.config.base_difficulty + (load + 1).ilog2()).min(self.config.max_difficulty)
+    }
+
+    /// Admit a call with a fresh proof of work of the current difficulty.
+    pub async fn check(
+        &self,
+        chain_id: u64,
+        input: &Bytes,
+        pow: Option<ProofOfWork>,
+    ) -> Result<(), RelayError> {
+        self.check_at(chain_id, input, pow, now()).await
+    }
+
+    async fn check_at(
+        &self,
+        chain_id: u64,
+        input: &Bytes,
+        pow: Option<ProofOfWork>,
+        now: u64,
+    ) -> Result<(), RelayError> {
+        let mut accepted = self.accepted.lock().await;
+
+        let difficulty = self.difficulty_at(&mut accepted, now);
+        let pow = pow.ok_or(RelayError::ProofOfWorkRequired { difficulty })?;
+        if pow.timestamp.abs_diff(now) > self.config.pow_ttl {
+            return Err(RelayError::StaleProofOfWork);
+        }
+        let digest = pow.digest(chain_id, input);
+        if leading_zeros(&digest) < difficulty {
+            return Err(RelayError::InsufficientWork { difficulty });
+        }
+
+        let mut stamps = self.stamps.lock().await;
+        stamps.retain(|_, expires_at| *expires_at > now);
+        if stamps.contains_key(&digest) {
+            return Err(RelayError::ReusedProofOfWork);
+        }
+        stamps.insert(digest, pow.timestamp + self.config.pow_ttl + 1);
+
+        accepted.push_back(now);
+        Ok(())
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn proof_of_work_gate() {
+        let gate = Gate::new(GateConfig {
+            base_difficulty: 8,
+            max_difficulty: 10,
+            target_per_minute: 2,
+            ..Default::default()
+        });
+        let input = Bytes::from_static(&[0xde, 0xad, 0xbe, 0xef]);
+        let now = 1718875852;
+
+        assert_eq!(
+            gate.check_at(10, &input, None, now).await,
+            Err(RelayError::ProofOfWorkRequired { difficulty: 8 })
+        );
+
+        let pow = ProofOfWork::solve(10, &input, now, 8);
+        // bound to the chain and calldata
+        assert_ne!(pow.digest(10, &input), pow.digest(11, &input));
+        assert_eq!(
+            gate.check_at(10, &input, Some(pow), now + 301).await,
+            Err(RelayError::StaleProofOfWork)
+        );
+        assert_eq!(gate.check_at(10, &input, Some(pow), now).await, Ok(()));
+        assert_eq!(
+            gate.check_at(10, &input, Some(pow), now).await,
+            Err(RelayError::ReusedProofOfWork)
+        );
+
+        // two accepted calls in the window double the load over the target
+        let pow = ProofOfWork::solve(10, &input, now + 1, 8);
+        assert_eq!(gate.check_at(10, &input, Some(pow), now + 1).await, Ok(()));
+        let mut accepted = gate.accepted.lock().await;
+        assert_eq!(gate.difficulty_at(&mut accepted, now + 1), 9);
+        assert_eq!(gate.difficulty_at(&mut accepted, now + LOAD_WINDOW), 8);
+    }
+}
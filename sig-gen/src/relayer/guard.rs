@@ -0,0 +1,196 @@
+use std::{future::Future, sync::Arc};
+
+use alloy::{
+    primitives::{Address, U256},
+    providers::{Provider, ReqwestProvider},
+};
+use anyhow::Result;
+use tokio::{
+    spawn,
+    sync::RwLock,
+    task::JoinHandle,
+    time::{interval, Duration},
+};
+use tracing::{error, info, warn};
+
+use super::RelayError;
+use crate::{config::SpendingLimits, contracts::EntryPoint};
+
+pub const DAY_SECONDS: u64 = 86400;
+
+/// Enforces the spending limits of a relayer wallet and keeps track of its
+/// balance. Relaying fails closed while the balance is unknown.
+#[derive(Debug, Clone)]
+pub struct Guard {
+    pub limits: SpendingLimits,
+    balance: Arc<RwLock<Option<U256>>>,
+}
+
+impl Guard {
+    pub fn new(limits: SpendingLimits) -> Self {
+        Guard {
+            limits,
+            balance: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// Poll the balance of `address` every 60 seconds, warning when it runs low.
+    pub fn watch(
+        &self,
+        chain_id: u64,
+        provider: ReqwestProvider,
+        address: Address,
+    ) -> JoinHandle<()> {
+        self.poll(chain_id, format!("relayer {}", address), move || {
+            let provider = provider.clone();
+            async move { Ok(provider.get_balance(address).await?) }
+        })
+    }
+
+    /// Poll the EntryPoint deposit of `paymaster` the same way.
+    pub fn watch_deposit(
+        &self,
+        chain_id: u64,
+        provider: ReqwestProvider,
+        entry_point: Address,
+        paymaster: Address,
+    ) -> JoinHandle<()> {
+        self.poll(chain_id, format!("paymaster {}", paymaster), move || {
+            let entry_point = EntryPoint::new(entry_point, provider.clone());
+            async move { Ok(entry_point.balanceOf(paymaster).call().await?._0) }
+        })
+    }
+
+    fn poll<F, Fut>(&self, chain_id: u64, payer: String, fetch: F) -> JoinHandle<()>
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<U256>> + Send,
+    {
+        let guard = self.clone();
+        spawn(async move {
+            let mut itv = interval(Duration::from_secs(60));
+            loop {
+                itv.tick().await;
+                match fetch().await {
+                    Ok(balance) => {
+                        if balance < U256::from(guard.limits.min_balance) {
+                            error!(
+                                "Chain {}: {} balance {} is under the minimum {}, relaying stopped",
+                                chain_id, payer, balance, guard.limits.min_balance
+                            );
+                        } else if balance < U256::from(guard.limits.low_balance) {
+                            warn!(
+                                "Chain {}: {} balance {} is low, top up",
+                                chain_id, payer, balance
+                            );
+                        } else {
+                            info!("Chain {}: {} balance {}", chain_id, payer, balance);
+                        }
+                        guard.set_balance(balance).await;
+                    }
+                    Err(e) => {
+                        error!(
+                            "Chain {}: failed to fetch {} balance: {}",
+                            chain_id, payer, e
+                        );
+                    }
+                }
+            }
+        })
+    }
+
+    pub async fn set_balance(&self, balance: U256) {
+        *self.balance.write().await = Some(balance);
+    }
+
+    pub async fn balance(&self) -> Option<U256> {
+        *self.balance.read().await
+    }
+
+    /// Whether the balance is under `low_balance`, or unknown.
+    pub async fn is_low(&self) -> bool {
+        self.balance()
+            .await
+            .is_none_or(|balance| balance < U256::from(self.limits.low_balance))
+    }
+
+    pub async fn check_balance(&self) -> Result<(), RelayError> {
+        match self.balance().await {
+            Some(balance) if balance >= U256::from(self.limits.min_balance) => Ok(()),
+            _ => Err(RelayError::BalanceExhausted),
+        }
+    }
+
+    pub fn worst_case(&self) -> u128 {
+        self.limits.worst_case()
+    }
+
+    pub fn check_budget(&self, spent: u128, cost: u128) -> Result<(), RelayError> {
+        self.limits.check_budget(spent, cost)
+    }
+
+    pub fn check_gas(&self, gas_limit: u128) -> Result<(), RelayError> {
+        match self.limits.max_gas_limit {
+            Some(cap) if gas_limit > cap => Err(RelayError::GasLimitExceeded { gas_limit, cap }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Clamp `(max_fee_per_gas, max_priority_fee_per_gas)` to the caps.
+    pub fn cap_fees(&self, (max_fee, priority_fee): (u128, u128)) -> (u128, u128) {
+        let max_fee = self
+            .limits
+            .max_fee_per_gas
+            .map_or(max_fee, |cap| max_fee.min(cap));
+        let priority_fee = self
+            .limits
+            .max_priority_fee_per_gas
+            .map_or(priority_fee, |cap| priority_fee.min(cap))
+            .min(max_fee);
+        (max_fee, priority_fee)
+    }
+
+    /// Whether fees are within the caps, replacements beyond them are skipped.
+    pub fn within_caps(&self, (max_fee, priority_fee): (u128, u128)) -> bool {
+        self.cap_fees((max_fee, priority_fee)) == (max_fee, priority_fee)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits() {
+        let guard = Guard::new(SpendingLimits {
+            max_gas_limit: Some(500_000),
+            max_fee_per_gas: Some(100),
+            max_priority_fee_per_gas: Some(10),
+            daily_budget: Some(1_000),
+            ..Default::default()
+        });
+
+        assert_eq!(guard.cap_fees((150, 20)), (100, 10));
+        assert_eq!(guard.cap_fees((5, 8)), (5, 5));
+        assert!(guard.within_caps((100, 10)));
+        assert!(!guard.within_caps((101, 10)));
+
+        assert!(guard.check_gas(500_000).is_ok());
+        assert_eq!(
+            guard.check_gas(500_001),
+            Err(RelayError::GasLimitExceeded {
+                gas_limit: 500_001,
+                cap: 500_000
+            })
+        );
+
+        assert!(guard.check_budget(600, 400).is_ok());
+        assert_eq!(
+            guard.check_budget(600, 401),
+            Err(RelayError::BudgetExhausted)
+        );
+        assert!(Guard::new(SpendingLimits::default())
+            .check_budget(u128::MAX, 1)
+            .is_ok());
+    }
+}
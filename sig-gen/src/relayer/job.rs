@@ -0,0 +1,199 @@
+use alloy::primitives::{Address, Bytes, B256, U256};
+use serde::{Deserialize, Serialize};
+
+use super::DAY_SECONDS;
+
+/// How long finished jobs are kept, for status lookups and the daily budget.
+pub const JOB_RETENTION_SECONDS: u64 = DAY_SECONDS;
+
+/// A relayed call and every transaction broadcast for it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub id: String,
+    pub chain_id: u64,
+    /// Pool wallet relaying the job, unset on jobs queued before the pool.
+    #[serde(default)]
+    pub from: Option<Address>,
+    pub to: Address,
+    pub input: Bytes,
+    /// Wei sent along, by transfers rebalancing the pool.
+    #[serde(default)]
+    pub value: U256,
+    #[serde(flatten)]
+    pub status: JobStatus,
+    /// Relayer nonce the job is bound to once first broadcast.
+    pub nonce: Option<u64>,
+    pub gas_limit: Option<u128>,
+    /// Broadcasts sharing `nonce`, each replacing the previous one.
+    pub transactions: Vec<Broadcast>,
+    /// Held in the mixing pool until then.
+    #[serde(default)]
+    pub release_at: Option<u64>,
+    /// Failed broadcast attempts before the job got a nonce.
+    #[serde(default)]
+    pub attempts: u32,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Pending {
+        tx_hash: B256,
+    },
+    Mined {
+        tx_hash: B256,
+        block_number: u64,
+        /// EAS attestation created by an `attest` call.
+        uid: Option<B256>,
+        /// Wei paid for the transaction.
+        #[serde(default)]
+        fee: u128,
+    },
+    Reverted {
+        tx_hash: B256,
+        block_number: u64,
+        #[serde(default)]
+        fee: u128,
+    },
+    /// The nonce was consumed by a transaction the relayer did not track.
+    Replaced,
+    Failed {
+        reason: String,
+    },
+}
+
+impl JobStatus {
+    pub fn is_final(&self) -> bool {
+        !matches!(self, JobStatus::Queued | JobStatus::Pending { .. })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Broadcast {
+    pub tx_hash: B256,
+    pub max_fee_per_gas: u128,
+    pub max_priority_fee_per_gas: u128,
+    pub sent_at: u64,
+}
+
+impl Job {
+    pub fn new(id: String, chain_id: u64, to: Address, input: Bytes, now: u64) -> Self {
+        Job {
+            id,
+            chain_id,
+            from: None,
+            to,
+            input,
+            value: U256::ZERO,
+            status: JobStatus::Queued,
+            nonce: None,
+            gas_limit: None,
+            transactions: vec![],
+            release_at: None,
+            attempts: 0,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// Whether the job finished more than `JOB_RETENTION_SECONDS` ago. Its
+    /// first broadcast is then before the current day, so it no longer counts
+    /// to the budget.
+    pub fn expired(&self, now: u64) -> bool {
+        self.status.is_final() && self.updated_at + JOB_RETENTION_SECONDS <= now
+    }
+
+    /// Time of the first broadcast, which the job's spending is accounted to.
+    pub fn sent_at(&self) -> Option<u64> {
+        self.transactions.first().map(|b| b.sent_at)
+    }
+
+    /// Wei paid once mined, or the most the current broadcast may cost.
+    pub fn cost(&self) -> u128 {
+        match &self.status {
+            JobStatus::Mined { fee, .. } | JobStatus::Reverted { fee, .. } => *fee,
+            JobStatus::Pending { .. } => self
+                .transactions
+                .last()
+                .map(|b| b.max_fee_per_gas * self.gas_limit.unwrap_or_default())
+                .unwrap_or_default(),
+            _ => 0,
+        }
+    }
+
+    /// Fees of a replacement for the last broadcast, raised by `bump_percent`
+    /// and never below the current estimate.
+    pub fn bumped_fees(&self, estimate: (u128, u128), bump_percent: u128) -> (u128, u128) {
+        match self.transactions.last() {
+            Some(last) => (
+                estimate
+                    .0
+                    .max(last.max_fee_per_gas * (100 + bump_percent) / 100 + 1),
+                estimate
+                    .1
+                    .max(last.max_priority_fee_per_gas * (100 + bump_percent) / 100 + 1),
+            ),
+            None => estimate,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::{address, b256};
+
+    use super::*;
+
+    #[test]
+    fn replacement_fees_and_status_shape() {
+        let mut job = Job::new(
+            "00".to_string(),
+            10,
+            address!("BCfA04883f6E55355a44Be12B020B35286314EC2"),
+            Bytes::new(),
+            1718875852,
+        );
+        assert_eq!(job.bumped_fees((100, 10), 20), (100, 10));
+
+        let tx_hash = b256!("0101010101010101010101010101010101010101010101010101010101010101");
+        job.transactions.push(Broadcast {
+            tx_hash,
+            max_fee_per_gas: 100,
+            max_priority_fee_per_gas: 10,
+            sent_at: 1718875852,
+        });
+        job.status = JobStatus::Pending { tx_hash };
+        assert_eq!(job.bumped_fees((90, 5), 20), (121, 13));
+        assert_eq!(job.bumped_fees((200, 5), 20), (200, 13));
+        job.gas_limit = Some(50_000);
+        assert_eq!(job.cost(), 5_000_000);
+
+        let value = serde_json::to_value(&job).unwrap();
+        assert_eq!(value["status"], "pending");
+        assert_eq!(value["tx_hash"], tx_hash.to_string());
+        let job: Job = serde_json::from_value(value).unwrap();
+        assert_eq!(job.status, JobStatus::Pending { tx_hash });
+    }
+
+    #[test]
+    fn finished_jobs_expire() {
+        let mut job = Job::new(
+            "00".to_string(),
+            10,
+            address!("BCfA04883f6E55355a44Be12B020B35286314EC2"),
+            Bytes::new(),
+            1718875852,
+        );
+        let later = job.updated_at + JOB_RETENTION_SECONDS;
+        assert!(!job.expired(later));
+
+        job.status = JobStatus::Failed {
+            reason: "reverted in simulation".to_string(),
+        };
+        assert!(!job.expired(later - 1));
+        assert!(job.expired(later));
+    }
+}
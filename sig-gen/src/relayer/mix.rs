@@ -0,0 +1,68 @@
+use ark_std::rand::{rngs::OsRng, Rng};
+use serde::{Deserialize, Serialize};
+
+/// How calls opting into mixing are held before their first broadcast, so
+/// the time of a relayed transaction does not point back to its request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum Mixing {
+    /// Hold each call for a uniformly random delay.
+    Delay { min_seconds: u64, max_seconds: u64 },
+    /// Release every held call together at the end of each interval.
+    Batch { interval_seconds: u64 },
+}
+
+impl Mixing {
+    /// Time a call submitted at `now` is released.
+    pub fn release_at(&self, now: u64) -> u64 {
+        match *self {
+            Mixing::Delay {
+                min_seconds,
+                max_seconds,
+            } => now + OsRng.gen_range(min_seconds..=max_seconds.max(min_seconds)),
+            Mixing::Batch { interval_seconds } => {
+                let interval_seconds = interval_seconds.max(1);
+                now - now % interval_seconds + interval_seconds
+            }
+        }
+    }
+
+    /// Range of release times of a call submitted at `now`.
+    pub fn window(&self, now: u64) -> (u64, u64) {
+        match *self {
+            Mixing::Delay {
+                min_seconds,
+                max_seconds,
+            } => (now + min_seconds, now + max_seconds.max(min_seconds)),
+            Mixing::Batch { .. } => {
+                let release_at = self.release_at(now);
+                (release_at, release_at)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn release_times() {
+        let batch = Mixing::Batch {
+            interval_seconds: 300,
+        };
+        assert_eq!(batch.release_at(1718875852), 1718876100);
+        assert_eq!(batch.release_at(1718876100), 1718876400);
+        assert_eq!(batch.window(1718875852), (1718876100, 1718876100));
+
+        let delay = Mixing::Delay {
+            min_seconds: 30,
+            max_seconds: 600,
+        };
+        let (from, to) = delay.window(1718875852);
+        assert_eq!((from, to), (1718875882, 1718876452));
+        for _ in 0..100 {
+            assert!((from..=to).contains(&delay.release_at(1718875852)));
+        }
+    }
+}
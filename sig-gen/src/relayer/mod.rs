@@ -0,0 +1,18 @@
+pub mod error;
+pub use error::*;
+pub mod gate;
+pub use gate::*;
+pub mod guard;
+pub use guard::*;
+pub mod job;
+pub use job::*;
+pub mod mix;
+pub use mix::*;
+pub mod pool;
+pub use pool::*;
+pub mod preflight;
+pub use preflight::*;
+pub mod queue;
+pub use queue::*;
+pub mod user_op;
+pub use user_op::*;
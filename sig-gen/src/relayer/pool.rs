@@ -0,0 +1,344 @@
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    fmt::Display,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use alloy::{
+    primitives::{Address, U256},
+    providers::ReqwestProvider,
+    signers::local::PrivateKeySigner,
+};
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use tokio::{
+    spawn,
+    sync::RwLock,
+    task::JoinHandle,
+    time::{interval, Duration},
+};
+use tracing::{error, info, warn};
+
+use super::{Job, RelayError, Relayer};
+use crate::{
+    config::{RelayerConfig, SpendingLimits},
+    store::JsonLog,
+};
+
+/// Failures in a row after which a wallet gets no new jobs for a while.
+pub const MAX_FAILURES: u32 = 3;
+
+/// Recent outcomes of a wallet's worker.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Health {
+    /// Failed ticks and broadcasts since the last broadcast that went through.
+    pub failures: u32,
+    pub last_error: Option<String>,
+    pub failed_at: Option<u64>,
+    pub sent_at: Option<u64>,
+}
+
+impl Health {
+    pub fn fail(&mut self, e: impl Display, now: u64) {
+        self.failures += 1;
+        self.last_error = Some(e.to_string());
+        self.failed_at = Some(now);
+    }
+
+    pub fn sent(&mut self, now: u64) {
+        self.failures = 0;
+        self.sent_at = Some(now);
+    }
+
+    /// Whether the wallet takes new jobs. A failing wallet is left alone for
+    /// `cooldown` seconds after its last failure, then tried again.
+    pub fn is_healthy(&self, now: u64, cooldown: u64) -> bool {
+        self.failures < MAX_FAILURES || self.failed_at.is_some_and(|at| at + cooldown <= now)
+    }
+}
+
+/// Relays calls of one chain from several wallets, so transactions are not
+/// serialised on a single nonce. Each wallet has its own worker and balance
+/// watch, while jobs and the daily budget are shared by the pool.
+#[derive(Debug, Clone)]
+pub struct RelayerPool {
+    pub chain_id: u64,
+    pub config: RelayerConfig,
+    /// Limits of every wallet, whose daily budget is the pool's.
+    pub limits: SpendingLimits,
+    pub relayers: Vec<Relayer>,
+    jobs: Arc<RwLock<HashMap<String, Job>>>,
+}
+
+impl RelayerPool {
+    /// Start relaying from every signer, resuming the chain's unfinished jobs.
+    /// Queued jobs of no wallet of the pool are taken over by the first one.
+    pub fn new(
+        chain_id: u64,
+        provider: ReqwestProvider,
+        signers: Vec<PrivateKeySigner>,
+        jobs: &[Job],
+        log: JsonLog<Job>,
+        config: RelayerConfig,
+        limits: SpendingLimits,
+    ) -> Result<(Self, Vec<JoinHandle<()>>)> {
+        let addresses = signers
+            .iter()
+            .map(|signer| signer.address())
+            .collect::<Vec<_>>();
+        let first = *addresses
+            .first()
+            .ok_or_else(|| anyhow!("Chain {}: no relayer wallet", chain_id))?;
+
+        let mut pool_jobs = HashMap::new();
+        for job in jobs.iter().filter(|job| job.chain_id == chain_id) {
+            let mut job = job.clone();
+            if !job.status.is_final() && job.from.is_none_or(|from| !addresses.contains(&from)) {
+                if job.nonce.is_none() {
+                    job.from = Some(first);
+                } else {
+                    warn!(
+                        "Chain {}: job {} is pending from {:?}, which left the pool",
+                        chain_id, job.id, job.from
+                    );
+                }
+            }
+            pool_jobs.insert(job.id.clone(), job);
+        }
+        let jobs = Arc::new(RwLock::new(pool_jobs));
+
+        let mut handles = vec![];
+        let relayers = signers
+            .into_iter()
+            .map(|signer| {
+                let (relayer, worker) = Relayer::new(
+                    chain_id,
+                    provider.clone(),
+                    signer,
+                    jobs.clone(),
+                    log.clone(),
+                    config.clone(),
+                    limits.clone(),
+                );
+                handles.push(worker);
+                handles.push(
+                    relayer
+                        .guard
+                        .watch(chain_id, provider.clone(), relayer.address),
+                );
+                relayer
+            })
+            .collect::<Vec<_>>();
+
+        let pool = RelayerPool {
+            chain_id,
+            config,
+            limits,
+            relayers,
+            jobs,
+        };
+        if pool.config.rebalance && pool.relayers.len() > 1 {
+            let pool = pool.clone();
+            handles.push(spawn(async move {
+                let mut itv = interval(Duration::from_secs(60));
+                loop {
+                    itv.tick().await;
+                    pool.rebalance().await;
+                }
+            }));
+        }
+
+        Ok((pool, handles))
+    }
+
+    /// Wallet to relay the next call from: the healthy wallet above its
+    /// minimum balance with the fewest unfinished jobs, so the call does not
+    /// wait behind other nonces, and the richest one among equals.
+    pub async fn pick(&self) -> Result<&Relayer, RelayError> {
+        self.limits
+            .check_budget(self.spent_today().await, self.limits.worst_case())?;
+
+        let now = now();
+        let mut funded = false;
+        let mut picked = None;
+        for relayer in &self.relayers {
+            if relayer.guard.check_balance().await.is_err() {
+                continue;
+            }
+            funded = true;
+            if !relayer
+                .health()
+                .await
+                .is_healthy(now, self.config.stuck_seconds)
+            {
+                continue;
+            }
+
+            let rank = (
+                relayer.in_flight().await,
+                Reverse(relayer.guard.balance().await),
+            );
+            if picked.as_ref().is_none_or(|(best, _)| rank < *best) {
+                picked = Some((rank, relayer));
+            }
+        }
+
+        match picked {
+            Some((_, relayer)) => Ok(relayer),
+            None if funded => Err(RelayError::NoHealthyWallet),
+            None => Err(RelayError::BalanceExhausted),
+        }
+    }
+
+    /// Wei spent by the pool today, see `Relayer::spent_today`.
+    pub async fn spent_today(&self) -> u128 {
+        self.relayers[0].spent_today().await
+    }
+
+    /// Times between which the job is expected to be included: from its
+    /// release until it would have been replaced once.
+    pub fn inclusion_window(&self, job: &Job) -> (u64, u64) {
+        let (from, to) = match (job.release_at, self.config.mixing) {
+            (Some(_), Some(mixing)) => mixing.window(job.created_at),
+            (Some(release_at), None) => (release_at, release_at),
+            (None, _) => (job.created_at, job.created_at),
+        };
+        (
+            from,
+            to + self.config.poll_seconds + self.config.stuck_seconds,
+        )
+    }
+
+    pub async fn job(&self, id: &str) -> Option<Job> {
+        self.jobs.read().await.get(id).cloned()
+    }
+
+    /// Top up wallets under their low balance from the richest wallet of the
+    /// pool, warning when none can spare enough.
+    async fn rebalance(&self) {
+        let mut balances = vec![];
+        for relayer in &self.relayers {
+            balances.push(relayer.guard.balance().await);
+        }
+
+        for (i, recipient) in self.relayers.iter().enumerate() {
+            let Some(balance) = balances[i] else {
+                continue;
+            };
+            let low_balance = U256::from(recipient.guard.limits.low_balance);
+            if balance >= low_balance || self.funding(recipient.address).await {
+                continue;
+            }
+
+            let donor = (0..self.relayers.len())
+                .filter(|j| *j != i)
+                .max_by_key(|j| balances[*j].unwrap_or_default());
+            let Some((j, amount)) = donor
+                .and_then(|j| top_up(balance, balances[j]?, low_balance).map(|amount| (j, amount)))
+            else {
+                warn!(
+                    "Chain {}: relayer {} is low and no wallet of the pool can top it up",
+                    self.chain_id, recipient.address
+                );
+                continue;
+            };
+
+            match self.relayers[j].transfer(recipient.address, amount).await {
+                Ok(job) => {
+                    info!(
+                        "Chain {}: topping up relayer {} with {} wei from {} in job {}",
+                        self.chain_id, recipient.address, amount, self.relayers[j].address, job.id
+                    );
+                    balances[j] = balances[j].map(|balance| balance - amount);
+                }
+                Err(e) => error!(
+                    "Chain {}: failed to queue a top up of relayer {}: {}",
+                    self.chain_id, recipient.address, e
+                ),
+            }
+        }
+    }
+
+    /// Whether a top up of `address` is still unfinished.
+    async fn funding(&self, address: Address) -> bool {
+        self.jobs
+            .read()
+            .await
+            .values()
+            .any(|job| job.to == address && !job.value.is_zero() && !job.status.is_final())
+    }
+}
+
+/// Wei to send a wallet holding `balance` to bring it to twice `low_balance`,
+/// if the donor keeps at least as much.
+pub fn top_up(balance: U256, donor_balance: U256, low_balance: U256) -> Option<U256> {
+    let target = low_balance * U256::from(2);
+    let amount = target.checked_sub(balance)?;
+    (donor_balance >= target + amount).then_some(amount)
+}
+
+/// Every hour, drop the expired jobs of the pools and rewrite the job log
+/// with the latest state of the others. `others` are the jobs of chains
+/// without a pool, kept until they expire.
+pub fn compact_jobs(
+    pools: Vec<RelayerPool>,
+    mut others: Vec<Job>,
+    log: JsonLog<Job>,
+) -> JoinHandle<()> {
+    spawn(async move {
+        let mut itv = interval(Duration::from_secs(3600));
+        loop {
+            itv.tick().await;
+            let now = now();
+            // jobs before the log, in the order `Relayer::save` locks them
+            let mut jobs = vec![];
+            for pool in &pools {
+                jobs.push(pool.jobs.write().await);
+            }
+            for jobs in &mut jobs {
+                jobs.retain(|_, job| !job.expired(now));
+            }
+            others.retain(|job| !job.expired(now));
+
+            let kept = jobs.iter().flat_map(|jobs| jobs.values()).chain(&others);
+            if let Err(e) = log.rewrite(kept).await {
+                error!("Failed to compact the relayer job log: {}", e);
+            }
+        }
+    })
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn health_and_top_ups() {
+        let mut health = Health::default();
+        for _ in 0..MAX_FAILURES {
+            assert!(health.is_healthy(1718875852, 60));
+            health.fail("nonce too low", 1718875852);
+        }
+        assert!(!health.is_healthy(1718875911, 60));
+        assert!(health.is_healthy(1718875912, 60));
+        health.sent(1718875912);
+        assert!(health.is_healthy(1718875912, 60));
+
+        let low = U256::from(100);
+        assert_eq!(
+            top_up(U256::from(40), U256::from(360), low),
+            Some(U256::from(160))
+        );
+        assert_eq!(top_up(U256::from(40), U256::from(359), low), None);
+        assert_eq!(top_up(U256::from(250), U256::from(1000), low), None);
+    }
+}
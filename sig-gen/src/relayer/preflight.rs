@@ -0,0 +1,101 @@
+use alloy::{
+    primitives::{Address, Bytes, B256},
+    providers::{network::TransactionBuilder, Provider, ReqwestProvider},
+    rpc::types::TransactionRequest,
+    sol_types::SolCall,
+    transports::TransportError,
+};
+
+use super::RelayError;
+use crate::{
+    contracts::{decode_revert, AnonymousAttester, AnonymousAttesterErrors},
+    crypto::{verify_proof, PublicInputs},
+};
+
+/// AnonymousAttester call accepted by the relayer.
+#[derive(Debug, Clone)]
+pub enum RelayCall {
+    Attest(AnonymousAttester::attestCall),
+    Revoke(AnonymousAttester::revokeCall),
+}
+
+impl RelayCall {
+    pub fn schema(&self) -> B256 {
+        match self {
+            RelayCall::Attest(call) => call.schema,
+            RelayCall::Revoke(call) => call.schema,
+        }
+    }
+}
+
+/// Decode a relay request and simulate it from the relayer address. Only
+/// calls that would succeed pass, the attester's reverts (unknown schema,
+/// used nonce, ...) becoming the matching errors.
+pub async fn preflight(
+    provider: &ReqwestProvider,
+    attester: Address,
+    from: Address,
+    input: &Bytes,
+) -> Result<RelayCall, RelayError> {
+    let call = decode(input)?;
+
+    let tx_request = TransactionRequest::default()
+        .from(from)
+        .to(attester)
+        .with_input(input.clone());
+    provider
+        .call(&tx_request)
+        .await
+        .map_err(|e| simulation_error(&call, e))?;
+
+    Ok(call)
+}
+
+/// Verify the proof of an `attest` call natively, under `pubkey` as
+/// `curiaPubkey`, so invalid proofs are refused without an RPC. Runs the
+/// pairing, keep it off the async workers.
+pub fn check_proof(pubkey: [B256; 2], call: &RelayCall) -> Result<(), RelayError> {
+    if let RelayCall::Attest(call) = call {
+        let inputs = PublicInputs::from_call(pubkey, call).to_array();
+        verify_proof(&call.proof.proof, &inputs).map_err(|_| RelayError::InvalidProof)?;
+    }
+    Ok(())
+}
+
+/// Accept only `attest` and `revoke` calldata.
+pub fn decode(input: &Bytes) -> Result<RelayCall, RelayError> {
+    let selector: [u8; 4] = input
+        .get(..4)
+        .and_then(|s| s.try_into().ok())
+        .ok_or_else(|| RelayError::MalformedCalldata("missing selector".to_string()))?;
+    let malformed = |e: alloy::sol_types::Error| RelayError::MalformedCalldata(e.to_string());
+
+    match selector {
+        AnonymousAttester::attestCall::SELECTOR => Ok(RelayCall::Attest(
+            AnonymousAttester::attestCall::abi_decode(input, true).map_err(malformed)?,
+        )),
+        AnonymousAttester::revokeCall::SELECTOR => Ok(RelayCall::Revoke(
+            AnonymousAttester::revokeCall::abi_decode(input, true).map_err(malformed)?,
+        )),
+        _ => Err(RelayError::UnsupportedSelector(selector.into())),
+    }
+}
+
+fn simulation_error(call: &RelayCall, e: TransportError) -> RelayError {
+    match decode_revert(&e) {
+        Some(AnonymousAttesterErrors::NonceUsed(_)) => match call {
+            RelayCall::Attest(call) => RelayError::NonceUsed(call.proof.nonce),
+            RelayCall::Revoke(_) => RelayError::Reverted("NonceUsed"),
+        },
+        Some(AnonymousAttesterErrors::InvalidProof(_)) => RelayError::InvalidProof,
+        Some(AnonymousAttesterErrors::InvalidVerifier(_)) => RelayError::InvalidVerifier,
+        Some(AnonymousAttesterErrors::InvalidSchema(_)) => {
+            RelayError::SchemaNotAllowed(call.schema())
+        }
+        Some(AnonymousAttesterErrors::RevokerNotFound(_)) => {
+            RelayError::Reverted("RevokerNotFound")
+        }
+        Some(AnonymousAttesterErrors::InvalidRevoker(_)) => RelayError::Reverted("InvalidRevoker"),
+        _ => RelayError::SimulationFailed(e.to_string()),
+    }
+}
@@ -0,0 +1,561 @@
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use alloy::{
+    primitives::{Address, Bytes, B256, U256},
+    providers::{
+        network::{Ethereum, EthereumWallet, Network, TransactionBuilder},
+        Provider, ReqwestProvider,
+    },
+    rpc::types::{BlockId, Log, TransactionRequest},
+    signers::local::PrivateKeySigner,
+    sol_types::SolEvent,
+    transports::RpcError,
+};
+use anyhow::{anyhow, Result};
+use ark_std::rand::{rngs::OsRng, RngCore};
+use tokio::{
+    select, spawn,
+    sync::{Notify, RwLock},
+    task::JoinHandle,
+    time::{sleep, Duration},
+};
+use tracing::{error, info, warn};
+
+use super::{Broadcast, Guard, Health, Job, JobStatus, RelayError, DAY_SECONDS};
+use crate::{
+    config::{RelayerConfig, SpendingLimits},
+    contracts::EAS,
+    store::JsonLog,
+};
+
+/// Open the relayer job log at `path` and return the latest state of every
+/// job but the expired ones, which the log is compacted to.
+pub async fn open_jobs(path: impl AsRef<Path>) -> Result<(JsonLog<Job>, Vec<Job>)> {
+    let (log, snapshots) = JsonLog::<Job>::open(path).await?;
+    let mut jobs = HashMap::new();
+    for job in snapshots {
+        jobs.insert(job.id.clone(), job);
+    }
+    let now = now();
+    jobs.retain(|_, job| !job.expired(now));
+    log.rewrite(jobs.values()).await?;
+
+    Ok((log, jobs.into_values().collect()))
+}
+
+/// Relays calls of one chain from a single wallet of its pool. Jobs are
+/// broadcast one nonce after the other by a background worker, which replaces
+/// stuck transactions with higher fees and follows them until they are mined.
+#[derive(Debug, Clone)]
+pub struct Relayer {
+    pub chain_id: u64,
+    pub address: Address,
+    pub guard: Guard,
+    pub config: RelayerConfig,
+    /// Jobs of every wallet of the pool.
+    jobs: Arc<RwLock<HashMap<String, Job>>>,
+    health: Arc<RwLock<Health>>,
+    log: JsonLog<Job>,
+    wake: Arc<Notify>,
+}
+
+impl Relayer {
+    /// Start relaying from the signer's wallet, picking up its unfinished jobs
+    /// among the pool's `jobs`.
+    pub fn new(
+        chain_id: u64,
+        provider: ReqwestProvider,
+        signer: PrivateKeySigner,
+        jobs: Arc<RwLock<HashMap<String, Job>>>,
+        log: JsonLog<Job>,
+        config: RelayerConfig,
+        limits: SpendingLimits,
+    ) -> (Self, JoinHandle<()>) {
+        let relayer = Relayer {
+            chain_id,
+            address: signer.address(),
+            guard: Guard::new(limits),
+            config,
+            jobs,
+            health: Arc::new(RwLock::new(Health::default())),
+            log,
+            wake: Arc::new(Notify::new()),
+        };
+
+        let mut worker = Worker {
+            relayer: relayer.clone(),
+            provider,
+            wallet: EthereumWallet::new(signer),
+            next_nonce: None,
+        };
+        let handle = spawn(async move {
+            loop {
+                if let Err(e) = worker.tick().await {
+                    error!(
+                        "Chain {}: relayer {} tick failed: {}",
+                        worker.relayer.chain_id, worker.relayer.address, e
+                    );
+                    worker.relayer.health.write().await.fail(&e, now());
+                    worker.next_nonce = None;
+                }
+                select! {
+                    _ = worker.relayer.wake.notified() => {}
+                    _ = sleep(Duration::from_secs(worker.relayer.config.poll_seconds)) => {}
+                }
+            }
+        });
+
+        (relayer, handle)
+    }
+
+    /// Queue a call to `to` and return its job. Mixed calls are held in the
+    /// pool until their release time.
+    pub async fn submit(&self, to: Address, input: Bytes, mix: bool) -> Result<Job> {
+        let mut job = self.new_job(to, input);
+        if mix {
+            let mixing = self
+                .config
+                .mixing
+                .ok_or_else(|| anyhow!("Mixing is not enabled"))?;
+            job.release_at = Some(mixing.release_at(job.created_at));
+        }
+
+        self.queue(job).await
+    }
+
+    /// Queue a transfer of `value` wei to `to`.
+    pub async fn transfer(&self, to: Address, value: U256) -> Result<Job> {
+        let mut job = self.new_job(to, Bytes::new());
+        job.value = value;
+        self.queue(job).await
+    }
+
+    fn new_job(&self, to: Address, input: Bytes) -> Job {
+        let mut id = [0u8; 16];
+        OsRng.fill_bytes(&mut id);
+        let mut job = Job::new(hex::encode(id), self.chain_id, to, input, now());
+        job.from = Some(self.address);
+        job
+    }
+
+    async fn queue(&self, job: Job) -> Result<Job> {
+        self.save(job.clone()).await?;
+        self.wake.notify_one();
+        Ok(job)
+    }
+
+    /// Refuse new jobs once the wallet may not spend anymore.
+    pub async fn ready(&self) -> Result<(), RelayError> {
+        self.guard.check_balance().await?;
+        self.guard
+            .check_budget(self.spent_today().await, self.guard.worst_case())
+    }
+
+    /// Wei spent by the pool, or committed by its pending transactions, since
+    /// the start of the UTC day.
+    pub async fn spent_today(&self) -> u128 {
+        let now = now();
+        let today = now - now % DAY_SECONDS;
+        self.jobs
+            .read()
+            .await
+            .values()
+            .filter(|job| job.sent_at().is_some_and(|sent_at| sent_at >= today))
+            .map(|job| job.cost())
+            .sum()
+    }
+
+    /// Unfinished jobs of this wallet.
+    pub async fn in_flight(&self) -> usize {
+        self.jobs
+            .read()
+            .await
+            .values()
+            .filter(|job| job.from == Some(self.address) && !job.status.is_final())
+            .count()
+    }
+
+    pub async fn health(&self) -> Health {
+        self.health.read().await.clone()
+    }
+
+    async fn save(&self, mut job: Job) -> Result<()> {
+        job.updated_at = now();
+        // hold the jobs while logging, so `compact_jobs` sees every update
+        let mut jobs = self.jobs.write().await;
+        self.log.append(&job).await?;
+        jobs.insert(job.id.clone(), job);
+        Ok(())
+    }
+}
+
+struct Worker {
+    relayer: Relayer,
+    provider: ReqwestProvider,
+    wallet: EthereumWallet,
+    /// Next nonce to hand out, resynced from the chain when unknown.
+    next_nonce: Option<u64>,
+}
+
+impl Worker {
+    async fn tick(&mut self) -> Result<()> {
+        let now = now();
+        let mut jobs = self
+            .relayer
+            .jobs
+            .read()
+            .await
+            .values()
+            .filter(|job| job.from == Some(self.relayer.address) && !job.status.is_final())
+            // mixed jobs wait in the pool until their release
+            .filter(|job| job.release_at.is_none_or(|release_at| release_at <= now))
+            .cloned()
+            .collect::<Vec<_>>();
+        if jobs.is_empty() {
+            return Ok(());
+        }
+        // pending jobs by nonce first, then queued jobs in arrival order, except
+        // mixed ones which go by their random id so a batch is not sent in the
+        // order it was submitted
+        jobs.sort_by_key(|job| {
+            (
+                job.nonce.is_none(),
+                job.nonce,
+                job.release_at.map(|_| job.id.clone()),
+                job.created_at,
+            )
+        });
+
+        // read before any receipt, so a consumed nonce always has its receipt
+        let confirmed = self
+            .provider
+            .get_transaction_count(self.relayer.address)
+            .await?;
+        for job in jobs {
+            match job.status {
+                JobStatus::Queued => self.broadcast(job).await?,
+                JobStatus::Pending { .. } => self.follow(job, confirmed).await?,
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn nonce(&mut self) -> Result<u64> {
+        if let Some(nonce) = self.next_nonce {
+            return Ok(nonce);
+        }
+
+        let pending = self
+            .provider
+            .get_transaction_count(self.relayer.address)
+            .block_id(BlockId::pending())
+            .await?;
+        let tracked = self
+            .relayer
+            .jobs
+            .read()
+            .await
+            .values()
+            .filter(|job| job.from == Some(self.relayer.address))
+            .filter(|job| matches!(job.status, JobStatus::Pending { .. }))
+            .filter_map(|job| job.nonce)
+            .map(|nonce| nonce + 1)
+            .max()
+            .unwrap_or_default();
+        let nonce = pending.max(tracked);
+        info!(
+            "Chain {}: relayer {} nonce at {}",
+            self.relayer.chain_id, self.relayer.address, nonce
+        );
+        self.next_nonce = Some(nonce);
+        Ok(nonce)
+    }
+
+    /// First broadcast of a queued job. The signed transaction is bound to the
+    /// job before it is sent, so a send that fails without the node refusing
+    /// it is followed like any pending transaction instead of being signed
+    /// again with a fresh nonce.
+    async fn broadcast(&mut self, mut job: Job) -> Result<()> {
+        let signed = async {
+            let nonce = self.nonce().await?;
+            let tx_request = self.request(&job).with_nonce(nonce);
+            let gas_limit = match job.gas_limit {
+                Some(gas_limit) => gas_limit,
+                None => self.provider.estimate_gas(&tx_request).await? * 120 / 100,
+            };
+            let guard = &self.relayer.guard;
+            guard.check_gas(gas_limit)?;
+            let fees = self.provider.estimate_eip1559_fees(None).await?;
+            let (max_fee_per_gas, max_priority_fee_per_gas) =
+                guard.cap_fees((fees.max_fee_per_gas, fees.max_priority_fee_per_gas));
+            guard.check_budget(
+                self.relayer.spent_today().await,
+                gas_limit * max_fee_per_gas,
+            )?;
+            guard.check_balance().await?;
+
+            let (envelope, broadcast) = self
+                .sign(
+                    tx_request.with_gas_limit(gas_limit),
+                    max_fee_per_gas,
+                    max_priority_fee_per_gas,
+                )
+                .await?;
+            anyhow::Ok((nonce, gas_limit, envelope, broadcast))
+        }
+        .await;
+
+        let sent = match signed {
+            Ok((nonce, gas_limit, envelope, broadcast)) => {
+                let tx_hash = broadcast.tx_hash;
+                let mut pending = job.clone();
+                pending.nonce = Some(nonce);
+                pending.gas_limit = Some(gas_limit);
+                pending.transactions.push(broadcast);
+                pending.status = JobStatus::Pending { tx_hash };
+                self.relayer.save(pending).await?;
+
+                match self.provider.send_tx_envelope(envelope).await {
+                    Ok(_) => Ok((nonce, tx_hash)),
+                    // refused by the node, the job is queued again below
+                    Err(e @ RpcError::ErrorResp(_)) => Err(e.into()),
+                    Err(e) => {
+                        warn!(
+                            "Chain {}: job {} may not have been sent as {} with nonce {}: {}",
+                            self.relayer.chain_id, job.id, tx_hash, nonce, e
+                        );
+                        self.next_nonce = Some(nonce + 1);
+                        self.relayer.health.write().await.fail(&e, now());
+                        return Ok(());
+                    }
+                }
+            }
+            Err(e) => Err(e),
+        };
+
+        match sent {
+            Ok((nonce, tx_hash)) => {
+                info!(
+                    "Chain {}: job {} sent as {} with nonce {}",
+                    self.relayer.chain_id, job.id, tx_hash, nonce
+                );
+                self.next_nonce = Some(nonce + 1);
+                self.relayer.health.write().await.sent(now());
+                return Ok(());
+            }
+            // refused by the spending limits, fail closed
+            Err(e) if e.is::<RelayError>() => {
+                warn!(
+                    "Chain {}: job {} refused: {}",
+                    self.relayer.chain_id, job.id, e
+                );
+                job.status = JobStatus::Failed {
+                    reason: e.to_string(),
+                };
+            }
+            Err(e) => {
+                // the nonce may be stale, resync it before the next attempt
+                self.next_nonce = None;
+                self.relayer.health.write().await.fail(&e, now());
+                job.attempts += 1;
+                warn!(
+                    "Chain {}: job {} attempt {} failed: {}",
+                    self.relayer.chain_id, job.id, job.attempts, e
+                );
+                if job.attempts >= self.relayer.config.max_attempts {
+                    job.status = JobStatus::Failed {
+                        reason: e.to_string(),
+                    };
+                }
+            }
+        }
+
+        self.relayer.save(job).await
+    }
+
+    /// Look for a receipt of any broadcast of a pending job, and replace its
+    /// transaction once it has been pending for too long.
+    async fn follow(&mut self, mut job: Job, confirmed: u64) -> Result<()> {
+        for broadcast in job.transactions.iter().rev() {
+            let Some(receipt) = self
+                .provider
+                .get_transaction_receipt(broadcast.tx_hash)
+                .await?
+            else {
+                continue;
+            };
+
+            let tx_hash = broadcast.tx_hash;
+            let block_number = receipt.block_number.unwrap_or_default();
+            let fee = receipt.gas_used * receipt.effective_gas_price;
+            job.status = if receipt.status() {
+                JobStatus::Mined {
+                    tx_hash,
+                    block_number,
+                    uid: attested_uid(receipt.inner.logs(), job.to),
+                    fee,
+                }
+            } else {
+                JobStatus::Reverted {
+                    tx_hash,
+                    block_number,
+                    fee,
+                }
+            };
+            info!(
+                "Chain {}: job {} {:?}",
+                self.relayer.chain_id, job.id, job.status
+            );
+            return self.relayer.save(job).await;
+        }
+
+        let Some(nonce) = job.nonce else {
+            return Ok(());
+        };
+        if confirmed > nonce {
+            warn!(
+                "Chain {}: nonce {} of job {} was consumed by another transaction",
+                self.relayer.chain_id, nonce, job.id
+            );
+            job.status = JobStatus::Replaced;
+            return self.relayer.save(job).await;
+        }
+
+        let sent_at = job.transactions.last().map(|b| b.sent_at).unwrap_or(0);
+        if now() < sent_at + self.relayer.config.stuck_seconds {
+            return Ok(());
+        }
+
+        let fees = self.provider.estimate_eip1559_fees(None).await?;
+        let (max_fee_per_gas, max_priority_fee_per_gas) = job.bumped_fees(
+            (fees.max_fee_per_gas, fees.max_priority_fee_per_gas),
+            self.relayer.config.fee_bump_percent,
+        );
+        let guard = &self.relayer.guard;
+        let gas_limit = job.gas_limit.unwrap_or_default();
+        if !guard.within_caps((max_fee_per_gas, max_priority_fee_per_gas)) {
+            warn!(
+                "Chain {}: job {} is stuck at the fee cap",
+                self.relayer.chain_id, job.id
+            );
+            return Ok(());
+        }
+        if let Err(e) = guard.check_budget(
+            self.relayer.spent_today().await.saturating_sub(job.cost()),
+            gas_limit * max_fee_per_gas,
+        ) {
+            warn!(
+                "Chain {}: job {} cannot be replaced: {}",
+                self.relayer.chain_id, job.id, e
+            );
+            return Ok(());
+        }
+
+        let tx_request = self
+            .request(&job)
+            .with_nonce(nonce)
+            .with_gas_limit(gas_limit);
+        let (envelope, broadcast) = match self
+            .sign(tx_request, max_fee_per_gas, max_priority_fee_per_gas)
+            .await
+        {
+            Ok(signed) => signed,
+            Err(e) => {
+                warn!(
+                    "Chain {}: failed to replace job {}: {}",
+                    self.relayer.chain_id, job.id, e
+                );
+                return Ok(());
+            }
+        };
+        let tx_hash = broadcast.tx_hash;
+        let mut replaced = job.clone();
+        replaced.transactions.push(broadcast);
+        replaced.status = JobStatus::Pending { tx_hash };
+        self.relayer.save(replaced).await?;
+
+        match self.provider.send_tx_envelope(envelope).await {
+            Ok(_) => {
+                info!(
+                    "Chain {}: job {} replaced by {} at {} wei per gas",
+                    self.relayer.chain_id, job.id, tx_hash, max_fee_per_gas
+                );
+                Ok(())
+            }
+            // a previous broadcast may have just been mined, check again next tick
+            Err(e @ RpcError::ErrorResp(_)) => {
+                warn!(
+                    "Chain {}: failed to replace job {}: {}",
+                    self.relayer.chain_id, job.id, e
+                );
+                self.relayer.save(job).await
+            }
+            // the replacement may be out, keep following it with the others
+            Err(e) => {
+                warn!(
+                    "Chain {}: job {} may not have been replaced by {}: {}",
+                    self.relayer.chain_id, job.id, tx_hash, e
+                );
+                Ok(())
+            }
+        }
+    }
+
+    fn request(&self, job: &Job) -> TransactionRequest {
+        TransactionRequest::default()
+            .with_from(self.relayer.address)
+            .with_to(job.to)
+            .with_input(job.input.clone())
+            .with_value(job.value)
+            .with_chain_id(self.relayer.chain_id)
+    }
+
+    /// Sign a transaction at the given fees, along with its broadcast record.
+    async fn sign(
+        &self,
+        tx_request: TransactionRequest,
+        max_fee_per_gas: u128,
+        max_priority_fee_per_gas: u128,
+    ) -> Result<(<Ethereum as Network>::TxEnvelope, Broadcast)> {
+        let envelope = tx_request
+            .with_max_fee_per_gas(max_fee_per_gas)
+            .with_max_priority_fee_per_gas(max_priority_fee_per_gas)
+            .build(&self.wallet)
+            .await?;
+        let tx_hash = *envelope.tx_hash();
+
+        Ok((
+            envelope,
+            Broadcast {
+                tx_hash,
+                max_fee_per_gas,
+                max_priority_fee_per_gas,
+                sent_at: now(),
+            },
+        ))
+    }
+}
+
+/// UID of the EAS attestation made by `attester` among transaction logs.
+pub fn attested_uid(logs: &[Log], attester: Address) -> Option<B256> {
+    logs.iter().find_map(|log| {
+        EAS::Attested::decode_log(&log.inner, true)
+            .ok()
+            .filter(|event| event.attester == attester)
+            .map(|event| event.uid)
+    })
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
@@ -0,0 +1,556 @@
+use std::{
+    path::Path,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use alloy::{
+    primitives::{keccak256, Address, Bytes, B256, U256, U64},
+    rpc::types::Log,
+    signers::{local::PrivateKeySigner, SignerSync},
+    sol_types::{SolCall, SolValue},
+};
+use anyhow::{anyhow, Result};
+use ark_std::rand::{rngs::OsRng, RngCore};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::sync::Mutex;
+use tracing::error;
+
+use super::{attested_uid, Guard, RelayError, DAY_SECONDS};
+use crate::{
+    config::{SpendingLimits, UserOpConfig},
+    contracts::SimpleAccount,
+    store::JsonLog,
+};
+
+/// ERC-4337 v0.7 user operation, in the unpacked form of the bundler RPC.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserOperation {
+    pub sender: Address,
+    pub nonce: U256,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub factory: Option<Address>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub factory_data: Option<Bytes>,
+    pub call_data: Bytes,
+    pub call_gas_limit: U256,
+    pub verification_gas_limit: U256,
+    pub pre_verification_gas: U256,
+    pub max_fee_per_gas: U256,
+    pub max_priority_fee_per_gas: U256,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub paymaster: Option<Address>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub paymaster_verification_gas_limit: Option<U256>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub paymaster_post_op_gas_limit: Option<U256>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub paymaster_data: Option<Bytes>,
+    pub signature: Bytes,
+}
+
+/// Two 128 bits values packed in one word, as in `accountGasLimits` and `gasFees`.
+fn pack_u128(high: U256, low: U256) -> B256 {
+    let mut word = [0u8; 32];
+    word[..16].copy_from_slice(&high.to::<u128>().to_be_bytes());
+    word[16..].copy_from_slice(&low.to::<u128>().to_be_bytes());
+    word.into()
+}
+
+impl UserOperation {
+    pub fn init_code(&self) -> Bytes {
+        match self.factory {
+            Some(factory) => [
+                factory.as_slice(),
+                self.factory_data.as_ref().map_or(&[][..], |data| &data[..]),
+            ]
+            .concat()
+            .into(),
+            None => Bytes::new(),
+        }
+    }
+
+    pub fn paymaster_and_data(&self) -> Bytes {
+        match self.paymaster {
+            Some(paymaster) => [
+                paymaster.as_slice(),
+                &self.paymaster_gas_limits().0[..],
+                self.paymaster_data
+                    .as_ref()
+                    .map_or(&[][..], |data| &data[..]),
+            ]
+            .concat()
+            .into(),
+            None => Bytes::new(),
+        }
+    }
+
+    fn paymaster_gas_limits(&self) -> B256 {
+        pack_u128(
+            self.paymaster_verification_gas_limit.unwrap_or_default(),
+            self.paymaster_post_op_gas_limit.unwrap_or_default(),
+        )
+    }
+
+    fn account_gas_limits(&self) -> B256 {
+        pack_u128(self.verification_gas_limit, self.call_gas_limit)
+    }
+
+    fn gas_fees(&self) -> B256 {
+        pack_u128(self.max_priority_fee_per_gas, self.max_fee_per_gas)
+    }
+
+    /// `EntryPoint.getUserOpHash`, signed by the account owner.
+    pub fn hash(&self, entry_point: Address, chain_id: u64) -> B256 {
+        let packed = (
+            self.sender,
+            self.nonce,
+            keccak256(self.init_code()),
+            keccak256(&self.call_data),
+            self.account_gas_limits(),
+            self.pre_verification_gas,
+            self.gas_fees(),
+            keccak256(self.paymaster_and_data()),
+        )
+            .abi_encode();
+        keccak256((keccak256(packed), entry_point, U256::from(chain_id)).abi_encode())
+    }
+
+    /// `VerifyingPaymaster.getHash`, signed by the paymaster's verifying signer.
+    pub fn paymaster_hash(&self, chain_id: u64, valid_until: u64, valid_after: u64) -> B256 {
+        keccak256(
+            (
+                self.sender,
+                self.nonce,
+                keccak256(self.init_code()),
+                keccak256(&self.call_data),
+                self.account_gas_limits(),
+                U256::from_be_bytes(self.paymaster_gas_limits().0),
+                self.pre_verification_gas,
+                self.gas_fees(),
+                U256::from(chain_id),
+                self.paymaster.unwrap_or_default(),
+                U256::from(valid_until),
+                U256::from(valid_after),
+            )
+                .abi_encode(),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GasEstimate {
+    pre_verification_gas: U256,
+    verification_gas_limit: U256,
+    call_gas_limit: U256,
+    paymaster_verification_gas_limit: Option<U256>,
+    paymaster_post_op_gas_limit: Option<U256>,
+}
+
+/// Outcome of a user operation, from `eth_getUserOperationReceipt`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserOpReceipt {
+    pub success: bool,
+    #[serde(default)]
+    pub reason: Option<String>,
+    pub actual_gas_cost: U256,
+    #[serde(default)]
+    pub logs: Vec<Log>,
+    pub receipt: UserOpTransaction,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserOpTransaction {
+    pub transaction_hash: B256,
+    pub block_number: U64,
+}
+
+impl UserOpReceipt {
+    /// UID of the EAS attestation made by `attester` in the operation.
+    pub fn uid(&self, attester: Address) -> Option<B256> {
+        attested_uid(&self.logs, attester)
+    }
+}
+
+/// Entry of the log of sponsored user operations, with the most each may
+/// cost the paymaster.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sponsored {
+    pub user_op_hash: B256,
+    pub cost: u128,
+    pub sent_at: u64,
+}
+
+/// Relays calls as user operations of a smart account, with gas sponsored by
+/// a verifying paymaster and submitted to a bundler. Every operation uses a
+/// random nonce key, so operations never wait on each other's nonce.
+#[derive(Debug, Clone)]
+pub struct UserOpRelayer {
+    pub chain_id: u64,
+    pub config: UserOpConfig,
+    /// Limits of the network, with the paymaster deposit as balance.
+    pub guard: Guard,
+    owner: PrivateKeySigner,
+    paymaster_signer: PrivateKeySigner,
+    client: reqwest::Client,
+    sponsored: Arc<Mutex<Vec<Sponsored>>>,
+    log: JsonLog<Sponsored>,
+}
+
+impl UserOpRelayer {
+    /// Open the log of sponsored operations at `path`, which holds the
+    /// operations of the day charged to the budget.
+    pub async fn new(
+        chain_id: u64,
+        config: UserOpConfig,
+        limits: SpendingLimits,
+        owner: PrivateKeySigner,
+        paymaster_signer: PrivateKeySigner,
+        path: impl AsRef<Path>,
+    ) -> Result<Self> {
+        let (log, sponsored) = JsonLog::open(path).await?;
+        Ok(UserOpRelayer {
+            chain_id,
+            config,
+            guard: Guard::new(limits),
+            owner,
+            paymaster_signer,
+            client: reqwest::Client::new(),
+            sponsored: Arc::new(Mutex::new(sponsored)),
+            log,
+        })
+    }
+
+    /// Wrap a call to `to` in a sponsored user operation of the account and
+    /// send it to the bundler, within the fee and gas caps and the daily
+    /// budget of the paymaster. Returns the user operation hash.
+    pub async fn submit(
+        &self,
+        to: Address,
+        input: Bytes,
+        fees: (u128, u128),
+    ) -> Result<B256, RelayError> {
+        self.guard.check_balance().await?;
+        let fees = self.guard.cap_fees(fees);
+        let mut key = [0u8; 24];
+        OsRng.fill_bytes(&mut key);
+        let mut op = UserOperation {
+            sender: self.config.account,
+            nonce: U256::from_be_slice(&key) << 64,
+            call_data: SimpleAccount::executeCall {
+                dest: to,
+                value: U256::ZERO,
+                func: input,
+            }
+            .abi_encode()
+            .into(),
+            max_fee_per_gas: U256::from(fees.0),
+            max_priority_fee_per_gas: U256::from(fees.1),
+            paymaster: Some(self.config.paymaster),
+            paymaster_verification_gas_limit: Some(U256::from(100_000)),
+            paymaster_post_op_gas_limit: Some(U256::ZERO),
+            ..Default::default()
+        };
+
+        // estimate with well-formed signatures, they are only checked once sent
+        self.sign(&mut op).map_err(user_op_failed)?;
+        let estimate: GasEstimate = self
+            .rpc(
+                "eth_estimateUserOperationGas",
+                json!([op, self.config.entry_point]),
+            )
+            .await
+            .map_err(user_op_failed)?;
+        op.pre_verification_gas = estimate.pre_verification_gas;
+        op.verification_gas_limit = estimate.verification_gas_limit;
+        op.call_gas_limit = estimate.call_gas_limit;
+        if let Some(gas) = estimate.paymaster_verification_gas_limit {
+            op.paymaster_verification_gas_limit = Some(gas);
+        }
+        if let Some(gas) = estimate.paymaster_post_op_gas_limit {
+            op.paymaster_post_op_gas_limit = Some(gas);
+        }
+
+        let gas = [
+            op.pre_verification_gas,
+            op.verification_gas_limit,
+            op.call_gas_limit,
+            op.paymaster_verification_gas_limit.unwrap_or_default(),
+            op.paymaster_post_op_gas_limit.unwrap_or_default(),
+        ]
+        .iter()
+        .fold(0u128, |gas, limit| {
+            gas.saturating_add(limit.saturating_to())
+        });
+        self.guard.check_gas(gas)?;
+        let cost = gas.saturating_mul(fees.0);
+
+        // held until sent, so concurrent operations cannot overrun the budget
+        let mut sponsored = self.sponsored.lock().await;
+        let now = now();
+        let today = now - now % DAY_SECONDS;
+        if sponsored.iter().any(|s| s.sent_at < today) {
+            sponsored.retain(|s| s.sent_at >= today);
+            if let Err(e) = self.log.rewrite(sponsored.iter()).await {
+                error!(
+                    "Chain {}: failed to compact the user operation log: {}",
+                    self.chain_id, e
+                );
+            }
+        }
+        let spent = sponsored.iter().map(|s| s.cost).sum();
+        self.guard.check_budget(spent, cost)?;
+
+        self.sign(&mut op).map_err(user_op_failed)?;
+        let user_op_hash = self
+            .rpc(
+                "eth_sendUserOperation",
+                json!([op, self.config.entry_point]),
+            )
+            .await
+            .map_err(user_op_failed)?;
+
+        let entry = Sponsored {
+            user_op_hash,
+            cost,
+            sent_at: now,
+        };
+        if let Err(e) = self.log.append(&entry).await {
+            error!(
+                "Chain {}: failed to log user operation {}: {}",
+                self.chain_id, user_op_hash, e
+            );
+        }
+        sponsored.push(entry);
+        Ok(user_op_hash)
+    }
+
+    /// Receipt of a user operation, once it is included.
+    pub async fn receipt(&self, user_op_hash: B256) -> Result<Option<UserOpReceipt>> {
+        self.rpc("eth_getUserOperationReceipt", json!([user_op_hash]))
+            .await
+    }
+
+    /// Sponsor the operation with a paymaster signature valid from now for
+    /// `sponsor_seconds`, then sign it as the account owner.
+    fn sign(&self, op: &mut UserOperation) -> Result<()> {
+        let valid_after = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let valid_until = valid_after + self.config.sponsor_seconds;
+        let paymaster_hash = op.paymaster_hash(self.chain_id, valid_until, valid_after);
+        let paymaster_signature = self
+            .paymaster_signer
+            .sign_message_sync(paymaster_hash.as_slice())?;
+        op.paymaster_data = Some(
+            [
+                (U256::from(valid_until), U256::from(valid_after))
+                    .abi_encode()
+                    .as_slice(),
+                &paymaster_signature.as_bytes(),
+            ]
+            .concat()
+            .into(),
+        );
+
+        let hash = op.hash(self.config.entry_point, self.chain_id);
+        op.signature = self
+            .owner
+            .sign_message_sync(hash.as_slice())?
+            .as_bytes()
+            .into();
+        Ok(())
+    }
+
+    async fn rpc<R: DeserializeOwned>(&self, method: &str, params: Value) -> Result<R> {
+        let response = self
+            .client
+            .post(&self.config.bundler_url)
+            .json(&json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params }))
+            .send()
+            .await?
+            .json::<Value>()
+            .await?;
+
+        if let Some(error) = response.get("error") {
+            return Err(anyhow!("Bundler {} failed: {}", method, error));
+        }
+        Ok(serde_json::from_value(
+            response.get("result").cloned().unwrap_or_default(),
+        )?)
+    }
+}
+
+fn user_op_failed(e: anyhow::Error) -> RelayError {
+    RelayError::UserOpFailed(e.to_string())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+
+    use alloy::{
+        primitives::{address, Signature},
+        sol_types::SolCall,
+    };
+    use axum::{extract::State, routing::post, Json, Router};
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::config::ENTRY_POINT_V07;
+
+    const CHAIN_ID: u64 = 11155420;
+
+    /// Bundler standing in for a real one: it checks both signatures the way
+    /// the account and the paymaster would, and includes operations at once.
+    #[derive(Clone)]
+    struct StandIn {
+        owner: Address,
+        paymaster_signer: Address,
+        sent: Arc<Mutex<Vec<UserOperation>>>,
+    }
+
+    async fn bundler(State(stand_in): State<StandIn>, Json(request): Json<Value>) -> Json<Value> {
+        let params = request["params"].clone();
+        let result = match request["method"].as_str().unwrap() {
+            "eth_estimateUserOperationGas" => json!({
+                "preVerificationGas": "0xc350",
+                "verificationGasLimit": "0x186a0",
+                "callGasLimit": "0x30d40",
+                "paymasterVerificationGasLimit": "0xea60",
+            }),
+            "eth_sendUserOperation" => {
+                let op: UserOperation = serde_json::from_value(params[0].clone()).unwrap();
+                let entry_point: Address = serde_json::from_value(params[1].clone()).unwrap();
+
+                let paymaster_data = op.paymaster_data.clone().unwrap();
+                let valid_until = U256::from_be_slice(&paymaster_data[..32]).to::<u64>();
+                let valid_after = U256::from_be_slice(&paymaster_data[32..64]).to::<u64>();
+                let paymaster_signature = Signature::try_from(&paymaster_data[64..]).unwrap();
+                let account_signature = Signature::try_from(&op.signature[..]).unwrap();
+                let hash = op.hash(entry_point, CHAIN_ID);
+                if paymaster_signature
+                    .recover_address_from_msg(op.paymaster_hash(CHAIN_ID, valid_until, valid_after))
+                    .unwrap()
+                    != stand_in.paymaster_signer
+                    || account_signature.recover_address_from_msg(hash).unwrap() != stand_in.owner
+                {
+                    return Json(json!({
+                        "jsonrpc": "2.0",
+                        "id": 1,
+                        "error": { "code": -32507, "message": "invalid signature" },
+                    }));
+                }
+
+                stand_in.sent.lock().await.push(op);
+                json!(hash)
+            }
+            "eth_getUserOperationReceipt" => json!({
+                "success": true,
+                "actualGasCost": "0x1",
+                "logs": [],
+                "receipt": {
+                    "transactionHash": B256::repeat_byte(1),
+                    "blockNumber": "0x10",
+                },
+            }),
+            _ => Value::Null,
+        };
+
+        Json(json!({ "jsonrpc": "2.0", "id": 1, "result": result }))
+    }
+
+    #[tokio::test]
+    async fn sponsored_user_operation() -> Result<()> {
+        let owner = PrivateKeySigner::random();
+        let paymaster_signer = PrivateKeySigner::random();
+        let stand_in = StandIn {
+            owner: owner.address(),
+            paymaster_signer: paymaster_signer.address(),
+            sent: Arc::new(Mutex::new(vec![])),
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let bundler_url = format!("http://{}", listener.local_addr()?);
+        let app = Router::new()
+            .route("/", post(bundler))
+            .with_state(stand_in.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let path = temp_dir().join(format!("user-ops-{}.jsonl", std::process::id()));
+        let relayer = UserOpRelayer::new(
+            CHAIN_ID,
+            UserOpConfig {
+                bundler_url,
+                entry_point: ENTRY_POINT_V07,
+                account: address!("00000000000000000000000000000000000000aa"),
+                paymaster: address!("00000000000000000000000000000000000000bb"),
+                sponsor_seconds: 600,
+            },
+            SpendingLimits {
+                max_fee_per_gas: Some(1_500_000),
+                max_gas_limit: Some(500_000),
+                daily_budget: Some(1_000_000_000_000),
+                ..Default::default()
+            },
+            owner,
+            paymaster_signer,
+            &path,
+        )
+        .await?;
+        let attester = address!("789CC626C6f2157a23649B71a26f13BeB45CfEBb");
+        let input = Bytes::from_static(&[0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(
+            relayer
+                .submit(attester, input.clone(), (2_000_000, 1_000_000))
+                .await,
+            Err(RelayError::BalanceExhausted)
+        );
+
+        relayer.guard.set_balance(U256::from(10u128.pow(18))).await;
+        let user_op_hash = relayer
+            .submit(attester, input.clone(), (2_000_000, 1_000_000))
+            .await?;
+        // 410,000 gas at the capped fee leaves no room for a second operation
+        assert_eq!(
+            relayer
+                .submit(attester, input.clone(), (2_000_000, 1_000_000))
+                .await,
+            Err(RelayError::BudgetExhausted)
+        );
+
+        let sent = stand_in.sent.lock().await;
+        assert_eq!(sent.len(), 1);
+        let op = &sent[0];
+        assert_eq!(user_op_hash, op.hash(ENTRY_POINT_V07, CHAIN_ID));
+        assert_eq!(op.max_fee_per_gas, U256::from(1_500_000));
+        assert_eq!(op.call_gas_limit, U256::from(200_000));
+        assert_eq!(
+            op.paymaster_verification_gas_limit,
+            Some(U256::from(60_000))
+        );
+        assert_eq!(op.nonce & U256::from(u64::MAX), U256::ZERO);
+        let call = SimpleAccount::executeCall::abi_decode(&op.call_data, true)?;
+        assert_eq!((call.dest, call.func), (attester, input));
+
+        let receipt = relayer.receipt(user_op_hash).await?.unwrap();
+        assert!(receipt.success);
+        assert_eq!(receipt.receipt.block_number, U64::from(16));
+        assert_eq!(receipt.uid(attester), None);
+
+        let (_, logged) = JsonLog::<Sponsored>::open(&path).await?;
+        assert_eq!(logged.len(), 1);
+        assert_eq!(logged[0].cost, 410_000 * 1_500_000);
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,118 @@
+use std::{
+    fs::{self, create_dir_all},
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{Context, Result};
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::{
+    fs::{rename, File, OpenOptions},
+    io::AsyncWriteExt,
+    sync::Mutex,
+};
+use tracing::warn;
+
+/// Append-only JSON lines file. Every entry is written and flushed as it
+/// happens, and the whole log is replayed when the file is opened again.
+/// Owners `rewrite` it with a summary of its entries to keep it small.
+#[derive(Debug)]
+pub struct JsonLog<T> {
+    path: Arc<PathBuf>,
+    file: Arc<Mutex<File>>,
+    _entry: PhantomData<fn(T)>,
+}
+
+impl<T> Clone for JsonLog<T> {
+    fn clone(&self) -> Self {
+        JsonLog {
+            path: self.path.clone(),
+            file: self.file.clone(),
+            _entry: PhantomData,
+        }
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> JsonLog<T> {
+    /// Open the log at `path`, creating it if needed, and return its entries.
+    pub async fn open(path: impl AsRef<Path>) -> Result<(Self, Vec<T>)> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)?;
+        }
+
+        let entries = match fs::read_to_string(path) {
+            Ok(content) => content
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| !line.trim().is_empty())
+                .filter_map(|(i, line)| match serde_json::from_str(line) {
+                    Ok(entry) => Some(entry),
+                    // a crash may leave the last line half written
+                    Err(e) => {
+                        warn!("Skipping line {} of {}: {}", i + 1, path.display(), e);
+                        None
+                    }
+                })
+                .collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => vec![],
+            Err(e) => return Err(e).context(format!("Failed to read {}", path.display())),
+        };
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+
+        Ok((
+            JsonLog {
+                path: Arc::new(path.to_path_buf()),
+                file: Arc::new(Mutex::new(file)),
+                _entry: PhantomData,
+            },
+            entries,
+        ))
+    }
+
+    pub async fn append(&self, entry: &T) -> Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+
+        let mut file = self.file.lock().await;
+        file.write_all(&line).await?;
+        file.flush().await?;
+        Ok(())
+    }
+
+    /// Replace the whole log with `entries`. They are written to a temporary
+    /// file renamed over the log, so a crash leaves either version intact.
+    pub async fn rewrite<'a>(&self, entries: impl IntoIterator<Item = &'a T>) -> Result<()>
+    where
+        T: 'a,
+    {
+        let mut content = vec![];
+        for entry in entries {
+            serde_json::to_writer(&mut content, entry)?;
+            content.push(b'\n');
+        }
+        let mut tmp = self.path.as_os_str().to_owned();
+        tmp.push(".tmp");
+
+        let mut file = self.file.lock().await;
+        let mut new = File::create(&tmp)
+            .await
+            .with_context(|| format!("Failed to create {}", tmp.to_string_lossy()))?;
+        new.write_all(&content).await?;
+        new.sync_all().await?;
+        rename(&tmp, self.path.as_ref()).await?;
+        *file = OpenOptions::new()
+            .append(true)
+            .open(self.path.as_ref())
+            .await
+            .with_context(|| format!("Failed to open {}", self.path.display()))?;
+        Ok(())
+    }
+}